@@ -0,0 +1,36 @@
+//! Optional payload compression, gated behind the `compression` feature so
+//! the default build stays dependency-free.
+//!
+//! Uses the heatshrink (LZSS-style) algorithm, which -- like the rest of
+//! this crate -- runs entirely over caller-provided buffers with no
+//! allocation, making it a reasonable fit for embedded targets.
+
+use crate::error::Error;
+use heatshrink::{decode, encode, Config};
+
+fn config() -> Config {
+    Config::default()
+}
+
+/// Try to compress `input` into `scratch`. Returns the compressed slice if
+/// it is strictly smaller than `input`, or `None` if compression didn't
+/// help (or `scratch` was too small to hold the result) -- callers should
+/// fall back to sending `input` uncompressed in that case.
+pub(crate) fn compress<'a>(input: &[u8], scratch: &'a mut [u8]) -> Option<&'a [u8]> {
+    match encode(input, scratch, &config()) {
+        Ok(compressed) if compressed.len() < input.len() => Some(compressed),
+        _ => None,
+    }
+}
+
+/// Decompress `input` (as produced by [`compress`]) into `out`.
+///
+/// `out` must be at least one byte larger than the expected decompressed
+/// size -- `heatshrink`'s decoder checks for a full output sink *before*
+/// flushing the final byte, so an exactly-sized buffer is reported as too
+/// small. Callers sizing `Frame<N>`/scratch buffers for compressed traffic
+/// should leave at least one byte of headroom above the largest payload they
+/// expect to receive.
+pub(crate) fn decompress<'a>(input: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error> {
+    decode(input, out, &config()).map_err(|_| Error::InvalidCompressedData)
+}
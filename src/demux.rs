@@ -0,0 +1,378 @@
+//! Stream multiplexing: route frames arriving on one physical link to
+//! independent logical channels by `id`, borrowing the partition idea from
+//! Kafka and QUIC stream IDs.
+//!
+//! [`crate::receiver::Receiver`] only exposes a single `receive()` path, so
+//! polling for one `id` while a frame for a different `id` is next on the
+//! wire would otherwise mean discarding it. `Demux` instead buffers
+//! out-of-turn frames per channel (head-of-line buffering), each channel
+//! bounded to `QUEUE_DEPTH` frames across at most `MAX_CHANNELS` distinct
+//! ids, so memory use stays fixed regardless of how bursty any one channel
+//! is. A channel slot stays assigned to its id once claimed, even after its
+//! queue drains.
+
+use crate::{
+    error::Error,
+    frame::Frame,
+    receiver::{ReceiveQueue, Receiver},
+};
+
+/// Fixed-depth ring buffer of at most `DEPTH` frames buffered for one `id`.
+/// `id` is `None` for a slot that hasn't been claimed by any channel yet.
+struct Channel<const N: usize, const DEPTH: usize> {
+    id: Option<u32>,
+    queue: [Option<Frame<N>>; DEPTH],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize, const DEPTH: usize> Channel<N, DEPTH> {
+    fn new() -> Self {
+        Self {
+            id: None,
+            queue: core::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Buffer `frame`. Returns it back unchanged if the channel is already
+    /// at `DEPTH` -- the caller decides whether to drop it.
+    fn push(&mut self, frame: Frame<N>) -> Result<(), Frame<N>> {
+        if self.len >= DEPTH {
+            return Err(frame);
+        }
+
+        let tail = (self.head + self.len) % DEPTH;
+        self.queue[tail] = Some(frame);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Frame<N>> {
+        let frame = self.queue[self.head].take()?;
+        self.head = (self.head + 1) % DEPTH;
+        self.len -= 1;
+        Some(frame)
+    }
+}
+
+/// Demultiplexes frames from one [`Receiver`] into up to `MAX_CHANNELS`
+/// independent logical channels, keyed by [`Frame::id`], each buffering up
+/// to `QUEUE_DEPTH` out-of-turn frames.
+pub struct Demux<RX, const N: usize, const MAX_CHANNELS: usize, const QUEUE_DEPTH: usize> {
+    receiver: Receiver<RX>,
+    channels: [Channel<N, QUEUE_DEPTH>; MAX_CHANNELS],
+    // Round-robin cursor for `receive_any()`, so repeated calls don't starve
+    // a channel just because another channel happens to come first in
+    // `channels`.
+    next_channel: usize,
+    // Frames silently dropped because their channel's queue was already
+    // full -- mirrors `Receiver::bytes_skipped` as a link-quality signal.
+    frames_dropped: u32,
+}
+
+impl<RX, const N: usize, const MAX_CHANNELS: usize, const QUEUE_DEPTH: usize>
+    Demux<RX, N, MAX_CHANNELS, QUEUE_DEPTH>
+where
+    RX: ReceiveQueue,
+{
+    pub fn new(receiver: Receiver<RX>) -> Self {
+        Self {
+            receiver,
+            channels: core::array::from_fn(|_| Channel::new()),
+            next_channel: 0,
+            frames_dropped: 0,
+        }
+    }
+
+    /// Total frames dropped so far because their channel's `QUEUE_DEPTH`
+    /// was already full when they arrived.
+    pub fn frames_dropped(&self) -> u32 {
+        self.frames_dropped
+    }
+
+    fn channel_index_for(&mut self, id: u32) -> Option<usize> {
+        if let Some(index) = self.channels.iter().position(|c| c.id == Some(id)) {
+            return Some(index);
+        }
+
+        let free = self.channels.iter().position(|c| c.id.is_none())?;
+        self.channels[free].id = Some(id);
+        Some(free)
+    }
+
+    /// Pull at most one frame off the wire and route it into its channel's
+    /// buffer. Returns `WouldBlock` if there's nothing to read right now, or
+    /// [`Error::TooManyChannels`] if the frame's `id` is new and every
+    /// channel slot is already claimed by a different `id` (slots are never
+    /// released). The frame is discarded in that case.
+    fn pump(&mut self) -> nb::Result<(), Error> {
+        let frame: Frame<N> = self.receiver.receive()?;
+        let id = frame.id();
+
+        let index = self
+            .channel_index_for(id)
+            .ok_or(nb::Error::Other(Error::TooManyChannels))?;
+
+        if self.channels[index].push(frame).is_err() {
+            self.frames_dropped += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Receive the next frame addressed to channel `id`, buffering any
+    /// other ids' frames that arrive in the meantime rather than discarding
+    /// them.
+    pub fn receive_on(&mut self, id: u32) -> nb::Result<Frame<N>, Error> {
+        if let Some(frame) = self.pop_from(id) {
+            return Ok(frame);
+        }
+
+        loop {
+            self.pump()?;
+            if let Some(frame) = self.pop_from(id) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    fn pop_from(&mut self, id: u32) -> Option<Frame<N>> {
+        let index = self.channels.iter().position(|c| c.id == Some(id))?;
+        self.channels[index].pop()
+    }
+
+    /// Receive the next available frame from any channel, round-robining
+    /// across channels with buffered frames so repeatedly calling this
+    /// doesn't starve a channel that's drained less eagerly than others.
+    pub fn receive_any(&mut self) -> nb::Result<(u32, Frame<N>), Error> {
+        if let Some(result) = self.pop_any_buffered() {
+            return Ok(result);
+        }
+
+        loop {
+            self.pump()?;
+            if let Some(result) = self.pop_any_buffered() {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn pop_any_buffered(&mut self) -> Option<(u32, Frame<N>)> {
+        for offset in 0..MAX_CHANNELS {
+            let index = (self.next_channel + offset) % MAX_CHANNELS;
+            let Some(id) = self.channels[index].id else {
+                continue;
+            };
+            if let Some(frame) = self.channels[index].pop() {
+                self.next_channel = (index + 1) % MAX_CHANNELS;
+                return Some((id, frame));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Demux;
+    use crate::frame::Frame;
+    use crate::receiver::{ReceiveQueue, Receiver};
+
+    struct DummyReceiver<'a> {
+        data: &'a [u8],
+        rx_count: &'a mut usize,
+    }
+    impl ReceiveQueue for DummyReceiver<'_> {
+        fn bytes_available(&self) -> usize {
+            self.data.len() - *self.rx_count
+        }
+
+        fn peek_at(&self, offset: usize) -> Option<u8> {
+            let read_offset = *self.rx_count + offset;
+            if read_offset < self.data.len() {
+                Some(self.data[read_offset])
+            } else {
+                None
+            }
+        }
+
+        fn flush(&mut self, n_bytes: usize) {
+            *self.rx_count += n_bytes;
+        }
+    }
+
+    // Two back-to-back frames: id=0x10 carrying [1,2,3], then id=0x20
+    // carrying [4,5,6].
+    fn two_frame_bytes() -> [u8; 30] {
+        let mut data = [0_u8; 30];
+        let frame_a: Frame<3> = Frame::new(0x10, &[1, 2, 3]).expect("Valid frame");
+        let frame_b: Frame<3> = Frame::new(0x20, &[4, 5, 6]).expect("Valid frame");
+
+        let (header_a, header_a_len) = frame_a_header_bytes(&frame_a);
+        let mut pos = 0;
+        data[pos..pos + header_a_len].copy_from_slice(&header_a[0..header_a_len]);
+        pos += header_a_len;
+
+        let (header_b, header_b_len) = frame_a_header_bytes(&frame_b);
+        data[pos..pos + header_b_len].copy_from_slice(&header_b[0..header_b_len]);
+
+        data
+    }
+
+    // Test-only helper: re-encode a `Frame` to raw wire bytes via
+    // `Transmitter`, since `Frame` itself has no public `as_bytes()`.
+    fn frame_a_header_bytes<const N: usize>(frame: &Frame<N>) -> ([u8; 0xFF], usize) {
+        use crate::transmitter::{TransmitQueue, Transmitter};
+
+        struct VecTransmitter<'a> {
+            data: &'a mut [u8; 0xFF],
+            tx_count: &'a mut usize,
+        }
+        impl TransmitQueue for VecTransmitter<'_> {
+            fn space_available(&self) -> usize {
+                0xFF - *self.tx_count
+            }
+            fn write(&mut self, byte: u8) -> Result<(), u8> {
+                self.data[*self.tx_count] = byte;
+                *self.tx_count += 1;
+                Ok(())
+            }
+        }
+
+        let mut data = [0_u8; 0xFF];
+        let mut tx_count = 0;
+        let tx = VecTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+        transmitter.transmit_frame(frame).expect("Transmit failed!");
+        (data, tx_count)
+    }
+
+    #[test]
+    fn receive_on_buffers_other_channels_frames() {
+        let data = two_frame_bytes();
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &data,
+            rx_count: &mut rx_count,
+        };
+        let receiver = Receiver::new(rx);
+        let mut demux: Demux<_, 128, 4, 4> = Demux::new(receiver);
+
+        // Channel 0x20's frame is second on the wire; polling for it first
+        // must still pump (and buffer) channel 0x10's frame along the way.
+        let frame = demux.receive_on(0x20).expect("Receive failed!");
+        assert_eq!(&[4, 5, 6], frame.bytes());
+
+        let frame = demux.receive_on(0x10).expect("Receive failed!");
+        assert_eq!(&[1, 2, 3], frame.bytes());
+        assert_eq!(0, demux.frames_dropped());
+    }
+
+    #[test]
+    fn receive_on_blocks_when_no_data_available() {
+        let data = [];
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &data,
+            rx_count: &mut rx_count,
+        };
+        let receiver = Receiver::new(rx);
+        let mut demux: Demux<_, 128, 4, 4> = Demux::new(receiver);
+
+        let err = demux
+            .receive_on(0x10)
+            .expect_err("No data should still block");
+        assert_eq!(nb::Error::WouldBlock, err);
+    }
+
+    #[test]
+    fn receive_any_round_robins_across_channels() {
+        let data = two_frame_bytes();
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &data,
+            rx_count: &mut rx_count,
+        };
+        let receiver = Receiver::new(rx);
+        let mut demux: Demux<_, 128, 4, 4> = Demux::new(receiver);
+
+        // Each receive_any() call pumps frames off the wire only until one
+        // is buffered, so the first call routes and returns 0x10's frame and
+        // the second call does the same for 0x20's.
+        let (first_id, first_frame) = demux.receive_any().expect("Receive failed!");
+        assert_eq!(0x10, first_id);
+        assert_eq!(&[1, 2, 3], first_frame.bytes());
+
+        let (second_id, second_frame) = demux.receive_any().expect("Receive failed!");
+        assert_eq!(0x20, second_id);
+        assert_eq!(&[4, 5, 6], second_frame.bytes());
+    }
+
+    #[test]
+    fn too_many_channels_reports_error() {
+        // 3 frames, 3 distinct ids, but only room for 2 channels.
+        let mut data = [0_u8; 90];
+        let mut pos = 0;
+        for id in [0x10_u32, 0x20, 0x30] {
+            let frame: Frame<3> = Frame::new(id, &[1, 2, 3]).expect("Valid frame");
+            let (bytes, len) = frame_a_header_bytes(&frame);
+            data[pos..pos + len].copy_from_slice(&bytes[0..len]);
+            pos += len;
+        }
+        let data = &data[0..pos];
+
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data,
+            rx_count: &mut rx_count,
+        };
+        let receiver = Receiver::new(rx);
+        let mut demux: Demux<_, 128, 2, 4> = Demux::new(receiver);
+
+        demux.receive_on(0x10).expect("Receive failed!");
+        demux.receive_on(0x20).expect("Receive failed!");
+
+        let err = demux
+            .receive_on(0x30)
+            .expect_err("A third distinct id shouldn't fit in 2 channels");
+        assert_eq!(nb::Error::Other(crate::error::Error::TooManyChannels), err);
+    }
+
+    #[test]
+    fn queue_overflow_drops_frames_and_counts_them() {
+        // 3 frames for the same channel, but QUEUE_DEPTH is only 2: the
+        // first receive_on() pumps through all 3 (since none of them is for
+        // the polled id), so the 3rd must be dropped and counted.
+        let mut data = [0_u8; 90];
+        let mut pos = 0;
+        for payload in [[1_u8, 2, 3], [4, 5, 6], [7, 8, 9]] {
+            let frame: Frame<3> = Frame::new(0x10, &payload).expect("Valid frame");
+            let (bytes, len) = frame_a_header_bytes(&frame);
+            data[pos..pos + len].copy_from_slice(&bytes[0..len]);
+            pos += len;
+        }
+        let data = &data[0..pos];
+
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data,
+            rx_count: &mut rx_count,
+        };
+        let receiver = Receiver::new(rx);
+        let mut demux: Demux<_, 128, 4, 2> = Demux::new(receiver);
+
+        // Poll a different, never-arriving channel first so all 3 frames
+        // for 0x10 get pumped into its buffer before we ever drain it.
+        let err = demux.receive_on(0x99);
+        assert_eq!(Err(nb::Error::WouldBlock), err.map(|_| ()));
+
+        assert_eq!(1, demux.frames_dropped());
+        assert_eq!(&[1, 2, 3], demux.receive_on(0x10).unwrap().bytes());
+        assert_eq!(&[4, 5, 6], demux.receive_on(0x10).unwrap().bytes());
+    }
+}
@@ -3,6 +3,10 @@ pub enum Error {
     InvalidHeader,
     InvalidID,
     InvalidLength,
+    InvalidFrameType,
+    InvalidCompressedData,
+    InvalidFragmentOffset,
+    TooManyChannels,
     InvalidCRC,
     InvalidBase64,
     QueueUnderflow,
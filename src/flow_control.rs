@@ -0,0 +1,132 @@
+//! Window-based flow control, modeled on neqo's `ReceiverFlowControl`/
+//! `SenderFlowControl`: a slow consumer advertises how many more bytes it's
+//! willing to buffer (`max_data`), and a sender stops emitting `Data` frames
+//! once it has used up that credit, resuming only once a
+//! [`crate::frame::FrameType::WindowUpdate`] frame raises the limit.
+//!
+//! Unlike QUIC, this crate has no acknowledgements: "sent" and "delivered"
+//! are the same event here, so the sender's credit is spent as soon as a
+//! frame is handed to the `TransmitQueue`, not once some later ack arrives.
+
+/// Default receive window, analogous to neqo's 1 MiB `RX_STREAM_DATA_WINDOW`.
+/// Used as the initial `max_data` on both ends before any `WINDOW_UPDATE`
+/// has been exchanged, so a sender and receiver that haven't talked yet
+/// still agree on the starting budget.
+pub const DEFAULT_WINDOW: u64 = 1024 * 1024;
+
+/// Receive-side flow control: tracks how many payload bytes the application
+/// has consumed against the credit (`max_data`) most recently advertised to
+/// the peer, and decides when that credit needs to grow.
+pub struct ReceiverFlowControl {
+    max_data: u64,
+    bytes_consumed: u64,
+    window: u64,
+}
+
+impl ReceiverFlowControl {
+    pub fn new(window: u64) -> Self {
+        Self {
+            max_data: window,
+            bytes_consumed: 0,
+            window,
+        }
+    }
+
+    /// Record that `n` more payload bytes have been consumed by the
+    /// application.
+    pub fn consume(&mut self, n: usize) {
+        self.bytes_consumed += n as u64;
+    }
+
+    /// Returns the new `max_data` credit to advertise via a `WINDOW_UPDATE`
+    /// frame, once the application has drained roughly half of the current
+    /// window -- as neqo does, so a `WINDOW_UPDATE` isn't needed on every
+    /// single received frame. Returns `None` while there's still enough
+    /// headroom left.
+    pub fn window_update(&mut self) -> Option<u32> {
+        if self.bytes_consumed < self.max_data - self.window / 2 {
+            return None;
+        }
+
+        self.max_data = self.bytes_consumed + self.window;
+        Some(self.max_data.try_into().unwrap_or(u32::MAX))
+    }
+
+    pub fn max_data(&self) -> u64 {
+        self.max_data
+    }
+}
+
+/// Send-side flow control: caps outstanding bytes at whatever limit the peer
+/// has most recently advertised.
+pub struct SenderFlowControl {
+    max_data: u64,
+    bytes_sent: u64,
+}
+
+impl SenderFlowControl {
+    pub fn new(initial_max_data: u64) -> Self {
+        Self {
+            max_data: initial_max_data,
+            bytes_sent: 0,
+        }
+    }
+
+    /// Remaining credit before the peer's advertised limit is reached.
+    pub fn available(&self) -> u64 {
+        self.max_data.saturating_sub(self.bytes_sent)
+    }
+
+    /// Spend `n` bytes of credit.
+    pub fn reserve(&mut self, n: usize) {
+        self.bytes_sent += n as u64;
+    }
+
+    /// Apply a `WINDOW_UPDATE` credit update from the peer. A duplicate or
+    /// reordered update carrying a smaller value than one already applied
+    /// must not claw back credit that's already usable.
+    pub fn on_window_update(&mut self, max_data: u32) {
+        self.max_data = self.max_data.max(max_data as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReceiverFlowControl, SenderFlowControl};
+
+    #[test]
+    fn receiver_window_update_waits_for_half_the_window() {
+        let mut fc = ReceiverFlowControl::new(100);
+        fc.consume(49);
+        assert_eq!(None, fc.window_update());
+        fc.consume(1); // 50 consumed: half the window
+        assert_eq!(Some(150), fc.window_update());
+        assert_eq!(150, fc.max_data());
+    }
+
+    #[test]
+    fn receiver_window_update_only_fires_once_per_threshold() {
+        let mut fc = ReceiverFlowControl::new(100);
+        fc.consume(50);
+        assert_eq!(Some(150), fc.window_update());
+        assert_eq!(None, fc.window_update()); // no further consumption yet
+    }
+
+    #[test]
+    fn sender_blocks_once_credit_exhausted() {
+        let mut fc = SenderFlowControl::new(10);
+        assert_eq!(10, fc.available());
+        fc.reserve(10);
+        assert_eq!(0, fc.available());
+    }
+
+    #[test]
+    fn sender_grows_on_window_update_but_never_shrinks() {
+        let mut fc = SenderFlowControl::new(10);
+        fc.reserve(10);
+        fc.on_window_update(5); // stale/duplicate: must not claw back credit
+        assert_eq!(0, fc.available());
+        fc.on_window_update(20);
+        assert_eq!(10, fc.available());
+    }
+}
@@ -5,18 +5,103 @@ use crc::{Crc, CRC_16_USB};
 pub struct Frame<const N: usize> {
     header: FrameHeader,
     data: [u8; N],
+    data_len: usize, // NB: length of the logical (decompressed) payload in `data`
 }
 
 #[derive(Debug)]
 pub(crate) struct FrameHeader {
-    id: u16,
-    length: u16, // NB: length of base64-data
+    id: u32,
+    frame_type: FrameType,
+    compressed: bool,
+    offset: u32,          // NB: byte offset of this fragment within the logical message
+    final_fragment: bool, // NB: whether this is the last fragment of the message
+    length: usize,        // NB: length of base64-data
+    header_len: usize,    // NB: encoded size of this header, in bytes
 }
 
+/// Identifies what a frame's payload means, following the opcode model used
+/// by WebSocket/HTTP/2 framing: control frames (`Ping`/`Pong`/`Close`/
+/// `WindowUpdate`) carry a tiny, optional payload through the same
+/// base64+CRC path as `Data` and let a link keep track of whether its peer
+/// is still alive, or how much flow-control credit it has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Ping,
+    Pong,
+    Close,
+    /// Advertises new flow-control credit -- see [`crate::flow_control`].
+    /// Carries the new cumulative `max_data` byte limit as a little-endian
+    /// `u32` payload.
+    WindowUpdate,
+}
+
+impl FrameType {
+    fn to_byte(self) -> u8 {
+        match self {
+            FrameType::Data => 0x00,
+            FrameType::Ping => 0x01,
+            FrameType::Pong => 0x02,
+            FrameType::Close => 0x03,
+            FrameType::WindowUpdate => 0x04,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0x00 => Ok(FrameType::Data),
+            0x01 => Ok(FrameType::Ping),
+            0x02 => Ok(FrameType::Pong),
+            0x03 => Ok(FrameType::Close),
+            0x04 => Ok(FrameType::WindowUpdate),
+            _ => Err(Error::InvalidFrameType),
+        }
+    }
+
+    /// Pack this opcode together with the `compressed`/`final_fragment`
+    /// flags into the header's single type byte. Mirrors the WebSocket
+    /// RSV-bit idea: the flags live alongside the opcode but stay well
+    /// below 0xF0 so they can never alias `START_OF_FRAME`/`END_OF_HEADER`.
+    fn to_type_byte(self, compressed: bool, final_fragment: bool) -> u8 {
+        self.to_byte()
+            | if compressed { COMPRESSED_FLAG } else { 0 }
+            | if final_fragment { FINAL_FRAGMENT_FLAG } else { 0 }
+    }
+
+    fn from_type_byte(byte: u8) -> Result<(Self, bool, bool), Error> {
+        let compressed = byte & COMPRESSED_FLAG != 0;
+        let final_fragment = byte & FINAL_FRAGMENT_FLAG != 0;
+        let frame_type = Self::from_byte(byte & !(COMPRESSED_FLAG | FINAL_FRAGMENT_FLAG))?;
+        Ok((frame_type, compressed, final_fragment))
+    }
+}
+
+/// Set in the header's type byte to indicate the payload was run through
+/// [`crate::compress`] before base64 encoding and must be inflated after CRC
+/// verification.
+const COMPRESSED_FLAG: u8 = 0x10;
+
+/// Set in the header's type byte when this is the last fragment of a
+/// (possibly multi-frame) logical message -- see [`crate::reassembly`].
+/// A single-frame message always sets this.
+const FINAL_FRAGMENT_FLAG: u8 = 0x20;
+
 pub const START_OF_FRAME: u8 = 0xF1;
 pub const END_OF_HEADER: u8 = 0xFF;
-pub const ID_MAX: u16 = 0xF0FF;
-pub const LENGTH_MAX: u16 = 0xF0FF;
+pub const ID_MAX: u32 = u32::MAX;
+pub const LENGTH_MAX: usize = usize::MAX;
+
+/// Every non-final byte of a varint-encoded field sets this continuation bit.
+const VARINT_CONTINUE: u8 = 0x40;
+/// Each varint byte only carries 6 payload bits, so every emitted byte stays
+/// below 0x80 and can never alias `START_OF_FRAME`/`END_OF_HEADER`.
+const VARINT_MASK: u8 = 0x3F;
+/// Enough groups to encode a full 64-bit value (ceil(64/6) = 11), which
+/// comfortably covers a `u32` id or a `usize` length on any realistic target.
+const MAX_VARINT_BYTES: usize = 11;
+
+/// `START_OF_FRAME` + varint(id) + frame-type byte + varint(offset) + varint(length) + `END_OF_HEADER`
+pub const HEADER_MAX_LEN: usize = 3 + 3 * MAX_VARINT_BYTES;
 
 fn div_round_up(a: usize, b: usize) -> usize {
     if a == 0 {
@@ -26,10 +111,60 @@ fn div_round_up(a: usize, b: usize) -> usize {
     (a - 1) / b + 1
 }
 
+/// Base-64 (6-bit) varint encoding: `value` is split into little-endian
+/// 6-bit groups, each emitted as one byte with the continuation bit
+/// (`VARINT_CONTINUE`) set on every group but the last. Returns the number
+/// of bytes written to `out`.
+fn encode_varint(mut value: u64, out: &mut [u8]) -> usize {
+    let mut count = 0;
+    loop {
+        let group = (value & VARINT_MASK as u64) as u8;
+        value >>= 6;
+
+        if value == 0 {
+            out[count] = group;
+            count += 1;
+            break;
+        }
+
+        out[count] = group | VARINT_CONTINUE;
+        count += 1;
+    }
+    count
+}
+
+/// Decode a varint from the start of `bytes`. Returns the decoded value and
+/// the number of bytes consumed, or `None` if `bytes` doesn't contain a
+/// complete, valid varint (no terminating byte within `MAX_VARINT_BYTES`, or
+/// a byte outside `0x00..=0x7F`).
+fn decode_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+    for (i, &byte) in bytes.iter().take(MAX_VARINT_BYTES).enumerate() {
+        if byte & 0x80 != 0 {
+            return None;
+        }
+        value |= ((byte & VARINT_MASK) as u64) << (6 * i);
+        if byte & VARINT_CONTINUE == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    value >>= 6;
+    while value > 0 {
+        len += 1;
+        value >>= 6;
+    }
+    len
+}
+
 impl FrameHeader {
-    fn calculate_length_field(payload_length: usize) -> Result<u16, Error> {
+    fn calculate_length_field(payload_length: usize) -> Result<usize, Error> {
         // Calculate size used when encoding the given data as a Frame:
-        // 6-byte header, 2-byte CRC, base64 overhead
+        // 2-byte CRC, base64 overhead
 
         // No payload: there won't be any CRC or base64 overhead
         if payload_length == 0 {
@@ -40,72 +175,186 @@ impl FrameHeader {
         if payload_length >= ((usize::MAX / 8) - 2) {
             return Err(Error::InvalidLength);
         }
-        let packet_length = div_round_up((payload_length + 2) * 8, 6);
-        if packet_length > LENGTH_MAX as usize {
-            return Err(Error::InvalidLength);
-        }
-        Ok(packet_length as u16)
+        Ok(div_round_up((payload_length + 2) * 8, 6))
+    }
+
+    /// Create a FrameHeader for a complete, unfragmented message (the common
+    /// case), calculating the length field based on `payload_length`.
+    pub fn new(
+        id: u32,
+        frame_type: FrameType,
+        compressed: bool,
+        payload_length: usize,
+    ) -> Result<Self, Error> {
+        Self::new_fragment(id, frame_type, compressed, 0, true, payload_length)
     }
 
-    /// Create a FrameHeader, calculating length field based on payload_length
-    pub fn new(id: u16, payload_length: usize) -> Result<Self, Error> {
+    /// Create a FrameHeader for one fragment of a (possibly multi-frame)
+    /// logical message -- see [`crate::reassembly`]. `offset` is this
+    /// fragment's byte offset within the reassembled message, and
+    /// `final_fragment` marks the fragment that completes it.
+    pub(crate) fn new_fragment(
+        id: u32,
+        frame_type: FrameType,
+        compressed: bool,
+        offset: u32,
+        final_fragment: bool,
+        payload_length: usize,
+    ) -> Result<Self, Error> {
         let length = Self::calculate_length_field(payload_length)?;
+        let header_len =
+            3 + varint_len(id as u64) + varint_len(offset as u64) + varint_len(length as u64);
+
+        Self::from_raw(
+            id,
+            frame_type,
+            compressed,
+            offset,
+            final_fragment,
+            length,
+            header_len,
+        )
+    }
 
-        Self::from_raw(id, length)
+    /// Create a FrameHeader from raw field values.
+    ///
+    /// `id` and `length` are already bounded by their types (`u32`/`usize`)
+    /// now that the varint encoding no longer needs to reserve any values to
+    /// avoid colliding with the marker bytes, so `ID_MAX`/`LENGTH_MAX` exist
+    /// purely as documentation of the (effectively unbounded) representable
+    /// range.
+    #[allow(clippy::too_many_arguments)]
+    fn from_raw(
+        id: u32,
+        frame_type: FrameType,
+        compressed: bool,
+        offset: u32,
+        final_fragment: bool,
+        length: usize,
+        header_len: usize,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            id,
+            frame_type,
+            compressed,
+            offset,
+            final_fragment,
+            length,
+            header_len,
+        })
     }
 
-    /// Create a FrameHeader from raw field values
-    fn from_raw(id: u16, length: u16) -> Result<Self, Error> {
-        if id > ID_MAX {
-            return Err(Error::InvalidID);
-        }
-        if length > LENGTH_MAX {
-            return Err(Error::InvalidLength);
-        }
+    pub fn frame_type(&self) -> FrameType {
+        self.frame_type
+    }
+
+    /// Whether the payload was compressed (see [`crate::compress`]) before
+    /// being base64-encoded, and must be inflated after CRC verification.
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
 
-        Ok(Self { id, length })
+    /// Byte offset of this fragment within the logical message it's part of
+    /// -- always `0` for a complete, unfragmented message.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Whether this is the last fragment of the logical message -- always
+    /// `true` for a complete, unfragmented message.
+    pub fn final_fragment(&self) -> bool {
+        self.final_fragment
     }
 
     pub fn data_len(&self) -> usize {
-        self.length as usize
+        self.length
+    }
+
+    /// Encoded size of the header itself, in bytes (markers + varints).
+    pub fn header_len(&self) -> usize {
+        self.header_len
     }
 
     pub fn total_packet_len(&self) -> usize {
-        6 + self.data_len()
+        self.header_len + self.data_len()
     }
 
     pub fn payload_len(&self) -> usize {
         // base64 to binary: 6 bits per character
         let binary_len = self.data_len() * 6 / 8;
 
-        if binary_len >= 2 {
-            // excluding 2-byte CRC
-            binary_len - 2
-        } else {
-            // No payload data
-            0
-        }
+        // excluding 2-byte CRC; saturates to 0 when there's no payload data
+        binary_len.saturating_sub(2)
     }
 
-    pub fn as_bytes(self: Self) -> [u8; 6] {
-        let id_bytes: [u8; 2] = self.id.to_le_bytes();
-        let len_bytes: [u8; 2] = self.length.to_le_bytes();
-        [
-            START_OF_FRAME,
-            id_bytes[0],
-            id_bytes[1],
-            len_bytes[0],
-            len_bytes[1],
-            END_OF_HEADER,
-        ]
+    /// Encode this header. Returns a fixed-capacity buffer together with the
+    /// number of leading bytes in it that are actually used.
+    pub fn as_bytes(&self) -> ([u8; HEADER_MAX_LEN], usize) {
+        let mut buf = [0_u8; HEADER_MAX_LEN];
+        let mut pos = 0;
+
+        buf[pos] = START_OF_FRAME;
+        pos += 1;
+
+        pos += encode_varint(self.id as u64, &mut buf[pos..]);
+
+        buf[pos] = self
+            .frame_type
+            .to_type_byte(self.compressed, self.final_fragment);
+        pos += 1;
+
+        pos += encode_varint(self.offset as u64, &mut buf[pos..]);
+
+        pos += encode_varint(self.length as u64, &mut buf[pos..]);
+
+        buf[pos] = END_OF_HEADER;
+        pos += 1;
+
+        debug_assert_eq!(pos, self.header_len);
+        (buf, pos)
     }
-}
 
-impl TryFrom<[u8; 6]> for FrameHeader {
-    type Error = Error;
+    /// Parse a header from the start of `bytes`, which may be followed by
+    /// trailing body bytes. Returns the header and the number of bytes it
+    /// occupied, so the caller knows where the body starts.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        if bytes.first() != Some(&START_OF_FRAME) {
+            return Err(Error::InvalidHeader);
+        }
+
+        let (id, id_len) = decode_varint(&bytes[1..]).ok_or(Error::InvalidHeader)?;
+        let id: u32 = id.try_into().map_err(|_| Error::InvalidID)?;
+
+        let type_pos = 1 + id_len;
+        let (frame_type, compressed, final_fragment) =
+            FrameType::from_type_byte(*bytes.get(type_pos).ok_or(Error::InvalidHeader)?)?;
+
+        let offset_start = type_pos + 1;
+        let (offset, offset_len) = decode_varint(bytes.get(offset_start..).unwrap_or(&[]))
+            .ok_or(Error::InvalidHeader)?;
+        let offset: u32 = offset.try_into().map_err(|_| Error::InvalidHeader)?;
+
+        let length_start = offset_start + offset_len;
+        let (length, length_len) = decode_varint(bytes.get(length_start..).unwrap_or(&[]))
+            .ok_or(Error::InvalidHeader)?;
+        let length: usize = length.try_into().map_err(|_| Error::InvalidLength)?;
+
+        let end_of_header_pos = length_start + length_len;
+        if bytes.get(end_of_header_pos) != Some(&END_OF_HEADER) {
+            return Err(Error::InvalidHeader);
+        }
 
-    fn try_from(slice: [u8; 6]) -> Result<Self, Self::Error> {
-        Self::try_from(&slice[0..6])
+        let header_len = end_of_header_pos + 1;
+        let header = Self::from_raw(
+            id,
+            frame_type,
+            compressed,
+            offset,
+            final_fragment,
+            length,
+            header_len,
+        )?;
+        Ok((header, header_len))
     }
 }
 
@@ -113,44 +362,51 @@ impl TryFrom<&[u8]> for FrameHeader {
     type Error = Error;
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        if slice.len() != 6 {
-            return Err(Error::TooFewBytes);
-        }
-        if slice.len() > 6 {
+        let (header, consumed) = Self::parse(slice)?;
+        if consumed < slice.len() {
             return Err(Error::TooManyBytes);
         }
+        Ok(header)
+    }
+}
 
-        // Parse start-of-frame marker
-        if slice[0] != START_OF_FRAME {
-            return Err(Error::InvalidHeader);
-        }
+impl<const N: usize> Frame<N> {
+    pub fn new(id: u32, payload: &[u8]) -> Result<Self, Error> {
+        Self::new_typed(id, FrameType::Data, payload)
+    }
 
-        // Parse ID
-        let id_bytes: [u8; 2] = slice[1..3].try_into().map_err(|_| Error::TooFewBytes)?;
-        let id = u16::from_le_bytes(id_bytes);
+    /// A keepalive frame; the peer is expected to reply with [`Self::pong`]
+    /// echoing the same `token`.
+    pub fn ping(id: u32, token: &[u8]) -> Result<Self, Error> {
+        Self::new_typed(id, FrameType::Ping, token)
+    }
 
-        // Parse length
-        let len_bytes: [u8; 2] = slice[3..5].try_into().map_err(|_| Error::TooFewBytes)?;
-        let length = u16::from_le_bytes(len_bytes);
+    /// Reply to a [`Self::ping`], echoing its `token`.
+    pub fn pong(id: u32, token: &[u8]) -> Result<Self, Error> {
+        Self::new_typed(id, FrameType::Pong, token)
+    }
 
-        // Parse end-of-header marker
-        if slice[5] != END_OF_HEADER {
-            return Err(Error::InvalidHeader);
-        }
+    /// Announces a clean link teardown; `reason` is an opaque, optional code.
+    pub fn close(id: u32, reason: &[u8]) -> Result<Self, Error> {
+        Self::new_typed(id, FrameType::Close, reason)
+    }
 
-        Self::from_raw(id, length)
+    /// Advertise new flow-control credit to the peer -- see
+    /// [`crate::flow_control`]. `max_data` is the new cumulative byte limit
+    /// the peer may now send up to.
+    pub fn window_update(id: u32, max_data: u32) -> Result<Self, Error> {
+        Self::new_typed(id, FrameType::WindowUpdate, &max_data.to_le_bytes())
     }
-}
 
-impl<const N: usize> Frame<N> {
-    pub fn new(id: u16, payload: &[u8]) -> Result<Self, Error> {
-        let header = FrameHeader::new(id, payload.len())?;
+    fn new_typed(id: u32, frame_type: FrameType, payload: &[u8]) -> Result<Self, Error> {
+        let header = FrameHeader::new(id, frame_type, false, payload.len())?;
 
         Ok({
             // pre-initialize
             let mut s = Self {
                 header,
                 data: [0; N],
+                data_len: payload.len(),
             };
 
             // copy data
@@ -162,13 +418,131 @@ impl<const N: usize> Frame<N> {
         })
     }
 
-    pub fn id(&self) -> u16 {
+    pub fn id(&self) -> u32 {
         self.header.id
     }
 
+    pub fn frame_type(&self) -> FrameType {
+        self.header.frame_type()
+    }
+
     pub fn bytes(&self) -> &[u8] {
-        &self.data[0..self.header.payload_len()]
+        &self.data[0..self.data_len]
     }
+
+    /// Byte offset of this frame within the logical message it's part of --
+    /// see [`crate::reassembly`]. Always `0` for a frame built by
+    /// [`Self::new`] and the other constructors; a frame parsed off the wire
+    /// carries the offset its sender set, e.g. via
+    /// [`crate::transmitter::Transmitter::transmit_fragment`].
+    pub(crate) fn offset(&self) -> u32 {
+        self.header.offset()
+    }
+
+    pub(crate) fn is_final_fragment(&self) -> bool {
+        self.header.final_fragment()
+    }
+}
+
+// `heatshrink`'s decoder needs its output buffer to be at least one byte
+// larger than the true decompressed length (see the note on
+// `crate::compress::decompress`), which would need to be expressed as
+// `[u8; N + 1]` here -- not possible on stable Rust for a generic `N`
+// without arithmetic on const generic parameters.
+//
+// Decompressing straight into `dest` (sized exactly `N`) still works for
+// the common case, where the true decompressed length is strictly less
+// than `N`: the decoder never has to address the one-past-the-end slot,
+// so the missing headroom never matters. It only misbehaves when the
+// decompressed length is *exactly* `N`, and that failure is ambiguous with
+// "more than `N`" -- both report the sink as full. So: try the cheap
+// direct path first, and only pay for a scratch buffer with real headroom
+// to disambiguate the two when that first attempt fails.
+#[cfg(feature = "compression")]
+const MAX_INFLATE_RETRY_LEN: usize = 4096;
+
+#[cfg(feature = "compression")]
+fn inflate<const N: usize>(wire: &[u8], dest: &mut [u8; N]) -> Result<usize, Error> {
+    if let Ok(out) = crate::compress::decompress(wire, dest) {
+        return Ok(out.len());
+    }
+
+    // Ambiguous: retry with headroom to find out whether the payload
+    // exactly filled `N` (fine) or overran it (not fine). Fixed-size
+    // rather than `N + 1` for the reason above -- this narrows (but can't
+    // eliminate) the ambiguity for frames whose `N` exceeds this bound.
+    let mut scratch = [0_u8; MAX_INFLATE_RETRY_LEN + 1];
+    let out_len = crate::compress::decompress(wire, &mut scratch)?.len();
+    if out_len > N {
+        return Err(Error::TooManyBytes);
+    }
+    dest[0..out_len].copy_from_slice(&scratch[0..out_len]);
+    Ok(out_len)
+}
+
+#[cfg(not(feature = "compression"))]
+fn inflate<const N: usize>(_wire: &[u8], _dest: &mut [u8; N]) -> Result<usize, Error> {
+    Err(Error::InvalidCompressedData)
+}
+
+/// Base64-decode `b64_data` into `dest` and verify the trailing CRC16.
+/// Returns the number of decoded (binary, pre-decompression) bytes written.
+fn decode_body<const N: usize>(
+    header: &FrameHeader,
+    b64_data: &[u8],
+    dest: &mut [u8; N],
+) -> Result<usize, Error> {
+    let b64_len = header.data_len();
+
+    // Last few bytes may not fit in the output buffer as the encoded data contain 2 extra bytes of CRC checksum.
+    // In base64 this is not guaranteed to be at a byte boundary, so we have to decode the last few bytes of data carefully!
+    let split_offset = if b64_len < 8 {
+        0
+    } else {
+        (b64_len - 4) & !3 // boundary at multiple of 4: 4 characters decode into exactly 3 bytes
+    };
+
+    // Decode bulk of the data directly into dest
+    let base64_cfg = base64::Config::new(base64::CharacterSet::Standard, false);
+    let bulk_decoded_size =
+        base64::decode_config_slice(&b64_data[0..split_offset], base64_cfg, dest)
+            .map_err(|_| Error::InvalidBase64)?;
+
+    // Decode last few bytes including CRC checksum
+    let mut last_bytes: [u8; 8] = [0; 8];
+    let remaining_len =
+        base64::decode_config_slice(&b64_data[split_offset..], base64_cfg, &mut last_bytes)
+            .map_err(|_| Error::InvalidBase64)?;
+
+    if remaining_len < 2 {
+        return Err(Error::TooFewBytes);
+    }
+
+    // Copy remaining data to dest
+    let remaining_data_len = remaining_len - 2;
+    let total_data_len = bulk_decoded_size + remaining_data_len;
+    debug_assert_eq!(total_data_len, header.payload_len());
+    for (i, byte) in last_bytes[0..remaining_data_len].iter().enumerate() {
+        dest[bulk_decoded_size + i] = *byte;
+    }
+
+    // Parse CRC
+    let crc_bytes = &last_bytes[remaining_data_len..remaining_len];
+    let crc_bytes: [u8; 2] = crc_bytes.try_into().map_err(|_| Error::TooFewBytes)?;
+    let parsed_crc = u16::from_le_bytes(crc_bytes);
+
+    // Verify CRC
+    // CRC16 checksum is calculated over all binary payload data
+    let crc = Crc::<u16>::new(&CRC_16_USB);
+    let mut checksum = crc.digest();
+    checksum.update(&dest[..total_data_len]);
+    let checksum = checksum.finalize();
+
+    if parsed_crc != checksum {
+        return Err(Error::InvalidCRC);
+    }
+
+    Ok(total_data_len)
 }
 
 // try_from header + slice
@@ -193,6 +567,7 @@ impl<const N: usize> TryFrom<(FrameHeader, &[u8])> for Frame<N> {
         let mut frame = Self {
             header,
             data: [0; N],
+            data_len: 0,
         };
 
         // No data to decode: frame is done
@@ -200,55 +575,16 @@ impl<const N: usize> TryFrom<(FrameHeader, &[u8])> for Frame<N> {
             return Ok(frame);
         }
 
-        // Last few bytes may not fit in the output buffer as the encoded data contain 2 extra bytes of CRC checksum.
-        // In base64 this is not guaranteed to be at a byte boundary, so we have to decode the last few bytes of data carefully!
-        let split_offset = if b64_len < 8 {
-            0
+        frame.data_len = if frame.header.compressed() {
+            // The wire bytes are the compressed payload, not the logical one:
+            // decode them into a scratch buffer first, then inflate into `data`.
+            let mut wire = [0_u8; N];
+            let wire_len = decode_body(&frame.header, b64_data, &mut wire)?;
+            inflate(&wire[0..wire_len], &mut frame.data)?
         } else {
-            (b64_len - 4) & !3 // boundary at multiple of 4: 4 characters decode into exactly 3 bytes
+            decode_body(&frame.header, b64_data, &mut frame.data)?
         };
 
-        // Decode bulk of the data directly into frame
-        let base64_cfg = base64::Config::new(base64::CharacterSet::Standard, false);
-        let bulk_decoded_size =
-            base64::decode_config_slice(&b64_data[0..split_offset], base64_cfg, &mut frame.data)
-                .map_err(|_| Error::InvalidBase64)?;
-
-        // Decode last few bytes including CRC checksum
-        let mut last_bytes: [u8; 8] = [0; 8];
-        let remaining_len =
-            base64::decode_config_slice(&b64_data[split_offset..], base64_cfg, &mut last_bytes)
-                .map_err(|_| Error::InvalidBase64)?;
-
-        if remaining_len < 2 {
-            return Err(Error::TooFewBytes);
-        }
-
-        // Copy remaining data to frame
-        let remaining_data_len = remaining_len - 2;
-        let total_data_len = bulk_decoded_size + remaining_data_len;
-        assert!(total_data_len == frame.header.payload_len());
-        for (i, byte) in last_bytes[0..remaining_data_len].iter().enumerate() {
-            frame.data[bulk_decoded_size + i] = *byte;
-        }
-
-        // Parse CRC
-        let crc_bytes = &last_bytes[remaining_data_len..remaining_len];
-        let crc_bytes: [u8; 2] = crc_bytes.try_into().map_err(|_| Error::TooFewBytes)?;
-        let parsed_crc = u16::from_le_bytes(crc_bytes);
-
-        // Verify CRC
-        // CRC16 checksum is calculated over all binary payload data
-        let crc = Crc::<u16>::new(&CRC_16_USB);
-        let mut checksum = crc.digest();
-        let len = frame.header.payload_len();
-        checksum.update(&frame.data[..len]);
-        let checksum = checksum.finalize();
-
-        if parsed_crc != checksum {
-            return Err(Error::InvalidCRC);
-        }
-
         Ok(frame)
     }
 }
@@ -258,8 +594,8 @@ impl<const N: usize> TryFrom<&[u8]> for Frame<N> {
     type Error = Error;
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        let header: FrameHeader = slice[0..6].try_into()?;
-        let b64_data = &slice[6..];
+        let (header, consumed) = FrameHeader::parse(slice)?;
+        let b64_data = &slice[consumed..];
 
         Self::try_from((header, b64_data))
     }
@@ -277,30 +613,38 @@ impl<const N: usize, const L: usize> TryFrom<&[u8; L]> for Frame<N> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Frame, END_OF_HEADER, START_OF_FRAME};
+    use super::{Frame, FrameType, END_OF_HEADER, START_OF_FRAME};
     use crate::error::Error;
 
-    fn valid_frame_bytes() -> [u8; 13] {
+    fn valid_frame_bytes() -> [u8; 12] {
         [
             // Frame header
             START_OF_FRAME, // Start-of-frame marker
-            0x37,           // packet ID 0x1337 as little-endian (low byte)
-            0x13,           // packet ID 0x1337 as little-endian (high byte)
-            0x07,           // Packet length 7 (4-byte data + 3-byte CRC) (low byte)
-            0x00,           // Packet length 7 (4-byte data + 3-byte CRC) (high byte)
+            0x77,           // varint(id = 0x1337) byte 0: 0b110111 | continuation
+            0x4C,           // varint(id = 0x1337) byte 1: 0b001100 | continuation
+            0x01,           // varint(id = 0x1337) byte 2 (terminator): 0b000001
+            0x20,           // frame type: Data, FINAL_FRAGMENT_FLAG set
+            0x00,           // varint(offset = 0) (terminator)
+            0x07,           // varint(length = 7) (terminator)
             END_OF_HEADER,  // End-of-header marker
             // base64-encoded [00, 01, 02] should be "AAEC" = [0x41, 0x41, 0x45, 0x43]
             0x41,
             0x41,
             0x45,
             0x43,
-            // CRC16-USB over [00, 01, 02] should be 0x6E0E = [0x0E, 0x6E] (little-endian) = "Dm4"
-            0x44,
-            0x6D,
-            0x34,
         ]
     }
 
+    /// Same as `valid_frame_bytes`, but with the full 3-character base64 CRC tail
+    fn valid_frame_bytes_full() -> [u8; 15] {
+        let mut out = [0_u8; 15];
+        out[0..12].copy_from_slice(&valid_frame_bytes());
+        out[12] = 0x44;
+        out[13] = 0x6D;
+        out[14] = 0x34;
+        out
+    }
+
     #[test]
     fn valid_new() {
         // Should be a valid frame containing 3 bytes
@@ -314,15 +658,30 @@ mod tests {
 
     #[test]
     fn valid_from_bytes() {
-        let frame = valid_frame_bytes();
+        let frame = valid_frame_bytes_full();
 
         // Should be a valid frame containing 3 bytes
         let _frame: Frame<3> = Frame::try_from(&frame).expect("Valid frame");
     }
 
+    #[test]
+    fn large_id_round_trips() {
+        // An id well above the old 16-bit / 0xF0FF cap must now work fine.
+        let frame: Frame<3> = Frame::new(0x12_34_56_78, &[9, 8, 7]).expect("Valid frame");
+        assert_eq!(0x12_34_56_78, frame.id());
+    }
+
+    #[test]
+    fn large_payload_round_trips() {
+        // A payload above the old ~45 KB cap (LENGTH_MAX = 0xF0FF) must now work.
+        let payload = [0x42_u8; 100_000];
+        let frame: Frame<100_000> = Frame::new(1, &payload).expect("Valid frame");
+        assert_eq!(&payload[..], frame.bytes());
+    }
+
     #[test]
     fn invalid_start_of_frame_from_bytes() {
-        let mut frame = valid_frame_bytes();
+        let mut frame = valid_frame_bytes_full();
         frame[0] = 0xF2; // invalid start-of-frame
 
         let err = Frame::<128>::try_from(&frame).expect_err("Should not be a valid frame header");
@@ -330,43 +689,56 @@ mod tests {
     }
     #[test]
     fn invalid_end_of_header_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[5] = START_OF_FRAME; // invalid end-of-header
+        let mut frame = valid_frame_bytes_full();
+        frame[7] = START_OF_FRAME; // invalid end-of-header
 
         let err = Frame::<128>::try_from(&frame).expect_err("Should not be a valid frame header");
         assert_eq!(Error::InvalidHeader, err);
     }
 
     #[test]
-    fn invalid_id_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[2] = START_OF_FRAME; // invalid ID: MSB cannot go >= 0xF0
+    fn invalid_length2_from_bytes() {
+        let mut frame = valid_frame_bytes_full();
+        frame[6] = 6; // wrong length: actual data is 7 bytes
+        frame[7] = END_OF_HEADER;
 
         let err = Frame::<128>::try_from(&frame).expect_err("Should not be a valid frame header");
-        assert_eq!(Error::InvalidID, err);
+        assert_eq!(Error::TooManyBytes, err);
     }
 
     #[test]
-    fn invalid_length_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[4] = START_OF_FRAME; // invalid Length: MSB cannot go >= 0xF0
+    fn invalid_frame_type_from_bytes() {
+        let mut frame = valid_frame_bytes_full();
+        frame[4] = 0x7F; // not a recognized FrameType
 
         let err = Frame::<128>::try_from(&frame).expect_err("Should not be a valid frame header");
-        assert_eq!(Error::InvalidLength, err);
+        assert_eq!(Error::InvalidFrameType, err);
     }
 
     #[test]
-    fn invalid_length2_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[3] = 6; // wrong length: actual data is 7 bytes
+    fn ping_pong_close_round_trip() {
+        let ping: Frame<4> = Frame::ping(0x1337, &[1, 2]).expect("Valid frame");
+        assert_eq!(FrameType::Ping, ping.frame_type());
+        assert_eq!(&[1, 2], ping.bytes());
 
-        let err = Frame::<128>::try_from(&frame).expect_err("Should not be a valid frame header");
-        assert_eq!(Error::TooManyBytes, err);
+        let pong: Frame<4> = Frame::pong(0x1337, &[1, 2]).expect("Valid frame");
+        assert_eq!(FrameType::Pong, pong.frame_type());
+
+        let close: Frame<4> = Frame::close(0x1337, &[0]).expect("Valid frame");
+        assert_eq!(FrameType::Close, close.frame_type());
+        assert_eq!(&[0], close.bytes());
+    }
+
+    #[test]
+    fn window_update_round_trips() {
+        let frame: Frame<4> = Frame::window_update(0x1337, 0x0011_2233).expect("Valid frame");
+        assert_eq!(FrameType::WindowUpdate, frame.frame_type());
+        assert_eq!(0x0011_2233, u32::from_le_bytes(frame.bytes().try_into().unwrap()));
     }
 
     #[test]
     fn frame_too_small_from_bytes() {
-        let frame = valid_frame_bytes();
+        let frame = valid_frame_bytes_full();
 
         // Frame defined impossibly small
         let err = Frame::<1>::try_from(&frame).expect_err("Should not be a valid frame header");
@@ -375,7 +747,7 @@ mod tests {
 
     #[test]
     fn frame_too_small2_from_bytes() {
-        let frame = valid_frame_bytes();
+        let frame = valid_frame_bytes_full();
 
         // Frame defined one byte too small
         let err = Frame::<2>::try_from(&frame).expect_err("Should not be a valid frame header");
@@ -384,24 +756,24 @@ mod tests {
 
     #[test]
     fn invalid_crc_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[6] = 0x42; // corrupt first byte
+        let mut frame = valid_frame_bytes_full();
+        frame[8] = 0x42; // corrupt first byte
 
         let err = Frame::<128>::try_from(&frame).expect_err("CRC should mismatch!");
         assert_eq!(Error::InvalidCRC, err);
     }
     #[test]
     fn invalid_crc2_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[9] = 0x42; // corrupt last byte
+        let mut frame = valid_frame_bytes_full();
+        frame[11] = 0x42; // corrupt last byte
 
         let err = Frame::<128>::try_from(&frame).expect_err("CRC should mismatch!");
         assert_eq!(Error::InvalidCRC, err);
     }
     #[test]
     fn invalid_crc3_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[11] = 0x42; // corrupt CRC byte
+        let mut frame = valid_frame_bytes_full();
+        frame[13] = 0x42; // corrupt CRC byte
 
         let err = Frame::<128>::try_from(&frame).expect_err("CRC should mismatch!");
         assert_eq!(Error::InvalidCRC, err);
@@ -409,10 +781,24 @@ mod tests {
 
     #[test]
     fn invalid_base64_from_bytes() {
-        let mut frame = valid_frame_bytes();
-        frame[11] = 0x80; // invalid base64 character
+        let mut frame = valid_frame_bytes_full();
+        frame[13] = 0x80; // invalid base64 character
 
         let err = Frame::<128>::try_from(&frame).expect_err("CRC should mismatch!");
         assert_eq!(Error::InvalidBase64, err);
     }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn compressed_flag_rejected_without_decompressor() {
+        // Same as `valid_frame_bytes_full`, but with the COMPRESSED_FLAG bit
+        // set on an otherwise-uncompressed body: decoding must fail cleanly
+        // rather than silently misinterpreting the bytes as compressed data
+        // that happens not to decompress.
+        let mut frame = valid_frame_bytes_full();
+        frame[4] |= super::COMPRESSED_FLAG;
+
+        let err = Frame::<128>::try_from(&frame).expect_err("Garbage isn't valid compressed data");
+        assert_eq!(Error::InvalidCompressedData, err);
+    }
 }
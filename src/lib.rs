@@ -0,0 +1,12 @@
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "compression")]
+mod compress;
+pub mod demux;
+pub mod error;
+pub mod flow_control;
+pub mod frame;
+pub mod reassembly;
+pub mod receiver;
+pub mod scanner;
+pub mod transmitter;
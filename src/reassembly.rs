@@ -0,0 +1,172 @@
+//! Out-of-order/multi-frame message reassembly, modeled on the way QUIC
+//! orders bytes arriving on a receive stream: a sender may split one logical
+//! message across several fragments (see
+//! [`crate::transmitter::Transmitter::transmit_fragment`]) that can arrive
+//! out of order, overlap on retransmission, or interleave with unrelated
+//! frames; [`Reassembler`] tracks which byte ranges have arrived and hands
+//! the completed message back once every byte up to the final fragment is in
+//! place.
+
+use heapless::Vec;
+
+use crate::error::Error;
+
+/// Max number of disjoint (not-yet-merged) byte ranges a [`Reassembler`]
+/// tracks at once. A pathologically out-of-order or lossy stream can exceed
+/// this before ranges get a chance to coalesce; reported as
+/// [`Error::TooManyBytes`] rather than silently dropping a fragment.
+const MAX_RANGES: usize = 16;
+
+/// Reassembles one logical message, up to `N` bytes, out of fragments that
+/// may arrive out of order or overlap.
+///
+/// Only one message (one `id`) is tracked at a time: a fragment for a
+/// different `id` resets any in-progress message and starts fresh. Several
+/// ids are not tracked concurrently; [`crate::demux::Demux`] routes whole
+/// frames by id but does not reassemble fragments.
+pub struct Reassembler<const N: usize> {
+    id: Option<u32>,
+    data: [u8; N],
+    // Sorted, non-overlapping, half-open [start, end) ranges of bytes received so far.
+    ranges: Vec<(usize, usize), MAX_RANGES>,
+    // Total message length, known once the final fragment has arrived.
+    total_len: Option<usize>,
+}
+
+impl<const N: usize> Reassembler<N> {
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            data: [0; N],
+            ranges: Vec::new(),
+            total_len: None,
+        }
+    }
+
+    fn reset(&mut self, id: u32) {
+        self.id = Some(id);
+        self.ranges.clear();
+        self.total_len = None;
+    }
+
+    /// Feed one fragment into the reassembler. Returns `Ok(Some(length))`
+    /// once every byte of `[0, length)` has arrived and a final fragment has
+    /// been seen -- the assembled bytes are then available via
+    /// [`Self::bytes`]. Returns `Ok(None)` if the message is still
+    /// incomplete.
+    ///
+    /// A fragment whose `offset + data.len()` doesn't fit in the `N`-byte
+    /// buffer is rejected with [`Error::InvalidFragmentOffset`] rather than
+    /// corrupting reassembly state.
+    pub fn insert(
+        &mut self,
+        id: u32,
+        offset: usize,
+        final_fragment: bool,
+        data: &[u8],
+    ) -> Result<Option<usize>, Error> {
+        if self.id != Some(id) {
+            self.reset(id);
+        }
+
+        let end = offset
+            .checked_add(data.len())
+            .filter(|&end| end <= N)
+            .ok_or(Error::InvalidFragmentOffset)?;
+
+        self.data[offset..end].copy_from_slice(data);
+
+        if final_fragment {
+            self.total_len = Some(end);
+        }
+
+        self.insert_range(offset, end)?;
+
+        Ok(self.total_len.filter(|&total| self.is_complete(total)))
+    }
+
+    /// Merge `[start, end)` into the sorted, non-overlapping range list,
+    /// coalescing with any range it overlaps or touches.
+    fn insert_range(&mut self, mut start: usize, mut end: usize) -> Result<(), Error> {
+        let mut i = 0;
+        while i < self.ranges.len() {
+            let (r_start, r_end) = self.ranges[i];
+            if r_start <= end && r_end >= start {
+                start = start.min(r_start);
+                end = end.max(r_end);
+                self.ranges.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        let pos = self.ranges.partition_point(|&(s, _)| s < start);
+        self.ranges
+            .insert(pos, (start, end))
+            .map_err(|_| Error::TooManyBytes)
+    }
+
+    fn is_complete(&self, total: usize) -> bool {
+        self.ranges.as_slice() == [(0, total)]
+    }
+
+    /// The assembled bytes, valid once [`Self::insert`] has returned
+    /// `Ok(Some(_))`.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl<const N: usize> Default for Reassembler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reassembler;
+    use crate::error::Error;
+
+    #[test]
+    fn in_order_fragments_reassemble() {
+        let mut r = Reassembler::<16>::new();
+        assert_eq!(Ok(None), r.insert(1, 0, false, &[1, 2, 3]));
+        assert_eq!(Ok(Some(6)), r.insert(1, 3, true, &[4, 5, 6]));
+        assert_eq!(&[1, 2, 3, 4, 5, 6], &r.bytes()[0..6]);
+    }
+
+    #[test]
+    fn out_of_order_fragments_reassemble() {
+        let mut r = Reassembler::<16>::new();
+        assert_eq!(Ok(None), r.insert(1, 3, true, &[4, 5, 6]));
+        assert_eq!(Ok(Some(6)), r.insert(1, 0, false, &[1, 2, 3]));
+        assert_eq!(&[1, 2, 3, 4, 5, 6], &r.bytes()[0..6]);
+    }
+
+    #[test]
+    fn duplicate_overlapping_fragment_is_idempotent() {
+        let mut r = Reassembler::<16>::new();
+        assert_eq!(Ok(None), r.insert(1, 0, false, &[1, 2, 3]));
+        assert_eq!(Ok(None), r.insert(1, 1, false, &[2, 3])); // overlaps, already covered
+        assert_eq!(Ok(Some(6)), r.insert(1, 2, true, &[3, 4, 5, 6]));
+        assert_eq!(&[1, 2, 3, 4, 5, 6], &r.bytes()[0..6]);
+    }
+
+    #[test]
+    fn fragment_exceeding_capacity_is_rejected() {
+        let mut r = Reassembler::<4>::new();
+        assert_eq!(
+            Err(Error::InvalidFragmentOffset),
+            r.insert(1, 2, true, &[1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn fragment_for_new_id_resets_in_progress_message() {
+        let mut r = Reassembler::<16>::new();
+        assert_eq!(Ok(None), r.insert(1, 0, false, &[1, 2, 3]));
+        assert_eq!(Ok(Some(2)), r.insert(2, 0, true, &[9, 9]));
+        assert_eq!(&[9, 9], &r.bytes()[0..2]);
+    }
+}
@@ -1,27 +1,59 @@
 use crate::{
     error::Error,
-    frame::{self, FrameHeader, START_OF_FRAME},
+    flow_control::{ReceiverFlowControl, DEFAULT_WINDOW},
+    frame::{self, FrameHeader, END_OF_HEADER, HEADER_MAX_LEN, START_OF_FRAME},
+    reassembly::Reassembler,
 };
 
 pub struct Receiver<RX> {
     bytes_skipped: u32,
     rx: RX,
+    flow_control: ReceiverFlowControl,
 }
 
 pub trait ReceiveQueue {
     fn bytes_available(&self) -> usize;
     fn peek_at(&self, offset: usize) -> Option<u8>;
     fn flush(&mut self, n_bytes: usize);
+
+    /// Scan forward from `start` for the next buffered byte equal to
+    /// `needle`, returning its offset (in the same indexing as `peek_at`),
+    /// or `None` if the buffered bytes run out before one is found.
+    ///
+    /// The default implementation just peeks one byte at a time. Backends
+    /// with a contiguous ring buffer should override this with a `memchr`
+    /// over the raw slice, so `Receiver` can resync past a span of garbage
+    /// with a single bulk `flush()` instead of skipping it byte by byte.
+    fn find_next(&self, start: usize, needle: u8) -> Option<usize> {
+        let mut offset = start;
+        loop {
+            match self.peek_at(offset) {
+                None => return None,
+                Some(byte) if byte == needle => return Some(offset),
+                Some(_) => offset += 1,
+            }
+        }
+    }
 }
 
 impl<RX> Receiver<RX>
 where
     RX: ReceiveQueue,
 {
+    /// Create a `Receiver` advertising [`DEFAULT_WINDOW`] bytes of
+    /// flow-control credit. Use [`Self::with_window`] to advertise a
+    /// different amount.
     pub fn new(rx: RX) -> Self {
+        Self::with_window(rx, DEFAULT_WINDOW)
+    }
+
+    /// Like [`Self::new`], but advertises `window` bytes of flow-control
+    /// credit instead of [`DEFAULT_WINDOW`] -- see [`crate::flow_control`].
+    pub fn with_window(rx: RX, window: u64) -> Self {
         Self {
             rx,
             bytes_skipped: 0,
+            flow_control: ReceiverFlowControl::new(window),
         }
     }
 
@@ -35,16 +67,25 @@ where
         self.bytes_skipped
     }
 
+    /// Returns new flow-control credit to advertise to the peer -- send it
+    /// with `Transmitter::transmit_frame(&Frame::window_update(id, credit)?)`
+    /// -- once the application has drained enough of the current window to
+    /// justify widening it. Returns `None` most of the time; callers should
+    /// poll after every successful `receive()`/`receive_reassembled()`.
+    pub fn poll_window_update(&mut self) -> Option<u32> {
+        self.flow_control.window_update()
+    }
+
     fn peek_bytes(
         &self,
         offset: usize,
         n: usize,
         result: &mut [u8],
     ) -> nb::Result<(), crate::error::Error> {
-        for i in 0..n {
+        for (i, slot) in result.iter_mut().enumerate().take(n) {
             match self.rx.peek_at(offset + i) {
                 Some(byte) => {
-                    result[i] = byte;
+                    *slot = byte;
                 }
                 None => {
                     return Err(nb::Error::Other(Error::QueueUnderflow));
@@ -60,33 +101,60 @@ where
     }
 
     fn rx_header(&mut self) -> nb::Result<FrameHeader, crate::error::Error> {
-        // Skip bytes untill START_OF_RAME is detected
-        loop {
-            match self.rx.peek_at(0) {
-                None => return Err(nb::Error::WouldBlock),
-                Some(START_OF_FRAME) => {
-                    break;
+        // Resync to the next START_OF_FRAME candidate. `find_next` lets
+        // backends with a contiguous ring buffer `memchr` for the marker,
+        // so the whole garbage span in front of it -- however it got there,
+        // be it a dropped connection or a corrupt packet -- is discarded
+        // with a single bulk `flush()` instead of one byte at a time.
+        match self.rx.find_next(0, START_OF_FRAME) {
+            Some(skip) => {
+                if skip > 0 {
+                    self.rx.flush(skip);
+                    self.bytes_skipped += skip as u32;
                 }
-                _ => {
-                    self.skip_byte();
+            }
+            None => {
+                // Nothing buffered could ever start a frame: it's all
+                // garbage, so drop it and wait for more to arrive.
+                let skip = self.rx.bytes_available();
+                if skip > 0 {
+                    self.rx.flush(skip);
+                    self.bytes_skipped += skip as u32;
                 }
+                return Err(nb::Error::WouldBlock);
             }
         }
 
-        // Wait untill enough data is available to form a packet header
-        if self.rx.bytes_available() < 6 {
-            return Err(nb::Error::WouldBlock);
+        // The header is variable-length (varint-encoded id and length), so
+        // peek byte-by-byte until END_OF_HEADER is found, bounded by the
+        // largest header that can ever be encoded.
+        let mut header_bytes = [0_u8; HEADER_MAX_LEN];
+        let mut len = 0;
+        loop {
+            if len >= HEADER_MAX_LEN {
+                // No END_OF_HEADER marker within the maximum header size: corrupt.
+                self.skip_byte();
+                return Err(nb::Error::Other(Error::InvalidHeader));
+            }
+
+            match self.rx.peek_at(len) {
+                None => return Err(nb::Error::WouldBlock),
+                Some(byte) => {
+                    header_bytes[len] = byte;
+                    len += 1;
+                    if byte == END_OF_HEADER {
+                        break;
+                    }
+                }
+            }
         }
 
-        // Build a packet header
-        let mut header_bytes = [0_u8; 6];
-        self.peek_bytes(0, 6, &mut header_bytes[0..])?;
-        match FrameHeader::try_from(header_bytes) {
+        match FrameHeader::try_from(&header_bytes[0..len]) {
             Ok(header) => Ok(header),
             Err(e) => {
                 // Header invalid: skip a byte and try again next time...
                 self.skip_byte();
-                return Err(nb::Error::Other(e));
+                Err(nb::Error::Other(e))
             }
         }
     }
@@ -106,12 +174,23 @@ where
             return Err(nb::Error::WouldBlock);
         }
 
+        let header_len = header.header_len();
         let mut data = [0_u8; N];
         let data_len = header.data_len();
-        self.peek_bytes(6, data_len, &mut data[0..])?;
+        let payload_len = header.payload_len();
+        self.peek_bytes(header_len, data_len, &mut data[0..])?;
         match frame::Frame::try_from((header, &data[0..data_len])) {
             Ok(frame) => {
                 self.rx.flush(total_len);
+                // Only `Data` payloads are subject to flow control -- mirrors
+                // `Transmitter::transmit_typed_fragment`, which only
+                // `reserve()`s credit for `Data` frames. Consuming credit for
+                // control frames here too would make the receiver advertise
+                // `WINDOW_UPDATE`s based on bytes the sender never counted
+                // against its own limit.
+                if frame.frame_type() == frame::FrameType::Data {
+                    self.flow_control.consume(payload_len);
+                }
                 Ok(frame)
             }
             Err(e) => {
@@ -121,11 +200,45 @@ where
             }
         }
     }
+
+    /// Receive one fragment of a (possibly multi-frame) logical message and
+    /// feed it into `reassembler`, returning the completed message as a
+    /// single `Frame` once every fragment up to and including the final one
+    /// has arrived. See [`crate::reassembly::Reassembler`] and
+    /// [`crate::transmitter::Transmitter::transmit_fragment`].
+    ///
+    /// `reassembler` is caller-owned and must be persisted across calls, the
+    /// same way a [`crate::transmitter::Transmitter`] persists its internal
+    /// send state. `M` and `N` are independent: `M` only needs to fit the
+    /// largest single fragment on the wire, while `N` -- the `Reassembler`'s
+    /// capacity -- is sized for the full reassembled message, which is
+    /// usually much larger.
+    pub fn receive_reassembled<const M: usize, const N: usize>(
+        &mut self,
+        reassembler: &mut Reassembler<N>,
+    ) -> nb::Result<frame::Frame<N>, crate::error::Error> {
+        let fragment: frame::Frame<M> = self.receive()?;
+
+        let total_len = reassembler.insert(
+            fragment.id(),
+            fragment.offset() as usize,
+            fragment.is_final_fragment(),
+            fragment.bytes(),
+        )?;
+
+        match total_len {
+            Some(total_len) => frame::Frame::new(fragment.id(), &reassembler.bytes()[0..total_len])
+                .map_err(nb::Error::Other),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::frame::{Frame, END_OF_HEADER, START_OF_FRAME};
+    use crate::reassembly::Reassembler;
+    use crate::transmitter::{TransmitQueue, Transmitter};
 
     use super::{ReceiveQueue, Receiver};
 
@@ -152,14 +265,76 @@ mod tests {
         }
     }
 
+    #[test]
+    fn poll_window_update_fires_once_half_the_window_is_consumed() {
+        let data = [
+            START_OF_FRAME,
+            0x77,
+            0x4C,
+            0x01,
+            0x20,
+            0x00,
+            0x07,
+            END_OF_HEADER,
+            0x41,
+            0x41,
+            0x45,
+            0x43,
+            0x44,
+            0x6D,
+            0x34,
+        ];
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &data,
+            rx_count: &mut rx_count,
+        };
+        // Window of 6: a single 3-byte payload consumes exactly half.
+        let mut receiver = Receiver::with_window(rx, 6);
+        let _frame: Frame<128> = receiver.receive().expect("Receive failed!");
+
+        assert_eq!(Some(9), receiver.poll_window_update());
+    }
+
+    #[test]
+    fn receive_does_not_consume_flow_control_for_control_frames() {
+        let mut wire = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = VecTransmitter {
+            data: &mut wire,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+        let ping: Frame<8> = Frame::ping(0x1337, &[1, 2, 3]).expect("Valid frame");
+        transmitter
+            .transmit_frame(&ping)
+            .expect("Transmit failed!");
+
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &wire[0..tx_count],
+            rx_count: &mut rx_count,
+        };
+        // A tiny window: if the `Ping` payload were (wrongly) consumed, this
+        // would immediately report a `WINDOW_UPDATE`.
+        let mut receiver = Receiver::with_window(rx, 6);
+        let _frame: Frame<8> = receiver.receive().expect("Receive failed!");
+
+        assert_eq!(None, receiver.poll_window_update());
+    }
+
+    // Frame header for id=0x1337, type=Data, offset=0, length=7: START_OF_FRAME,
+    // varint(0x1337) = [0x77, 0x4C, 0x01], 0x20, varint(0) = [0x00], varint(7) = [0x07], END_OF_HEADER
     #[test]
     fn receive_works() {
         let mut data = [
             START_OF_FRAME,
-            0x37,
-            0x13,
-            0x07,
+            0x77,
+            0x4C,
+            0x01,
+            0x20,
             0x00,
+            0x07,
             END_OF_HEADER,
             0x41,
             0x41,
@@ -190,10 +365,12 @@ mod tests {
         let data = [
             0x34,
             START_OF_FRAME,
-            0x37,
-            0x13,
-            0x07,
+            0x77,
+            0x4C,
+            0x01,
+            0x20,
             0x00,
+            0x07,
             END_OF_HEADER,
             0x41,
             0x41,
@@ -221,24 +398,31 @@ mod tests {
 
     #[test]
     fn receive_skip_any_works() {
+        // The first 12 bytes are filler, not a header: every byte has its
+        // MSB set, so as soon as one of them is read as the first byte
+        // after an (injected) START_OF_FRAME, varint decoding aborts
+        // immediately with InvalidHeader -- it can never be coincidentally
+        // re-parsed as a valid header for the real frame's body below.
         let data = [
-            0x37,
-            0x13,
-            0x07,
-            0x00,
-            END_OF_HEADER,
-            0x41,
-            0x41,
-            0x45,
-            0x43,
-            0x44,
-            0x6D,
-            0x34,
+            0x90,
+            0x91,
+            0x92,
+            0x93,
+            0x94,
+            0x95,
+            0x96,
+            0x97,
+            0x98,
+            0x99,
+            0x9A,
+            0x9B,
             START_OF_FRAME,
-            0x37,
-            0x13,
-            0x07,
+            0x77,
+            0x4C,
+            0x01,
+            0x20,
             0x00,
+            0x07,
             END_OF_HEADER,
             0x41,
             0x41,
@@ -272,4 +456,175 @@ mod tests {
             assert_eq!(2, frame.bytes()[2]);
         }
     }
+
+    #[test]
+    fn rx_header_resyncs_via_find_next_in_a_single_flush() {
+        // A backend that overrides `find_next` with a real memchr-style
+        // scan, so we can assert the garbage span in front of the real
+        // frame is discarded with one `flush()` call instead of one byte at
+        // a time.
+        struct MemchrReceiver<'a> {
+            data: &'a [u8],
+            rx_count: &'a mut usize,
+            flush_calls: &'a mut usize,
+        }
+        impl ReceiveQueue for MemchrReceiver<'_> {
+            fn bytes_available(&self) -> usize {
+                self.data.len() - *self.rx_count
+            }
+
+            fn peek_at(&self, offset: usize) -> Option<u8> {
+                let read_offset = *self.rx_count + offset;
+                if read_offset < self.data.len() {
+                    Some(self.data[read_offset])
+                } else {
+                    None
+                }
+            }
+
+            fn find_next(&self, start: usize, needle: u8) -> Option<usize> {
+                let read_offset = *self.rx_count + start;
+                self.data[read_offset..]
+                    .iter()
+                    .position(|&byte| byte == needle)
+            }
+
+            fn flush(&mut self, n_bytes: usize) {
+                *self.rx_count += n_bytes;
+                *self.flush_calls += 1;
+            }
+        }
+
+        let mut data = [0x90_u8; 32];
+        data[20] = START_OF_FRAME;
+        data[21] = 0x77;
+        data[22] = 0x4C;
+        data[23] = 0x01;
+        data[24] = 0x20;
+        data[25] = 0x00;
+        data[26] = 0x07;
+        data[27] = END_OF_HEADER;
+        data[28] = 0x41;
+        data[29] = 0x41;
+        data[30] = 0x45;
+        data[31] = 0x43;
+        // Intentionally short (4 of the 7 body bytes): receive() stops with
+        // WouldBlock right after parsing the header, so the only flush() is
+        // the resync one asserted on below.
+
+        let mut rx_count: usize = 0;
+        let mut flush_calls: usize = 0;
+        let rx = MemchrReceiver {
+            data: &data,
+            rx_count: &mut rx_count,
+            flush_calls: &mut flush_calls,
+        };
+        let mut receiver = Receiver::new(rx);
+
+        let _ = receiver.receive::<128>();
+
+        assert_eq!(20, receiver.bytes_skipped());
+        assert_eq!(
+            1, flush_calls,
+            "the whole garbage span should be flushed in one call"
+        );
+    }
+
+    struct VecTransmitter<'a> {
+        data: &'a mut [u8; 0xFFFF],
+        tx_count: &'a mut usize,
+    }
+    impl TransmitQueue for VecTransmitter<'_> {
+        fn space_available(&self) -> usize {
+            0xFFFF_usize - *self.tx_count
+        }
+
+        fn write(&mut self, byte: u8) -> Result<(), u8> {
+            if *self.tx_count >= 0xFFFF_usize {
+                return Err(byte);
+            }
+
+            self.data[*self.tx_count] = byte;
+            *self.tx_count += 1;
+            Ok(())
+        }
+    }
+
+    /// End-to-end: a message split into two fragments via
+    /// `Transmitter::transmit_fragment` reassembles into the original bytes
+    /// on the other end.
+    #[test]
+    fn transmit_fragment_and_receive_reassembled_round_trip() {
+        let mut wire = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = VecTransmitter {
+            data: &mut wire,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+        transmitter
+            .transmit_fragment(0x1337, 0, false, &[1, 2, 3])
+            .expect("Transmit failed!");
+        transmitter
+            .transmit_fragment(0x1337, 3, true, &[4, 5, 6])
+            .expect("Transmit failed!");
+
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &wire[0..tx_count],
+            rx_count: &mut rx_count,
+        };
+        let mut receiver = Receiver::new(rx);
+        let mut reassembler = Reassembler::<16>::new();
+
+        // Fragments are only 3 bytes each, so a much smaller scratch buffer
+        // than the 16-byte reassembled message suffices to receive them.
+        let _e = receiver
+            .receive_reassembled::<8, 16>(&mut reassembler)
+            .expect_err("First fragment alone is incomplete");
+        let frame: Frame<16> = receiver
+            .receive_reassembled::<8, 16>(&mut reassembler)
+            .expect("Receive failed!");
+
+        assert_eq!(0x1337, frame.id());
+        assert_eq!(&[1, 2, 3, 4, 5, 6], frame.bytes());
+    }
+
+    #[test]
+    fn receive_reassembled_handles_out_of_order_fragments() {
+        let mut wire = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = VecTransmitter {
+            data: &mut wire,
+            tx_count: &mut tx_count,
+        };
+        {
+            let mut transmitter = Transmitter::new(tx);
+            // Send the final fragment first.
+            transmitter
+                .transmit_fragment(0x1337, 3, true, &[4, 5, 6])
+                .expect("Transmit failed!");
+            transmitter
+                .transmit_fragment(0x1337, 0, false, &[1, 2, 3])
+                .expect("Transmit failed!");
+        }
+
+        let mut rx_count: usize = 0;
+        let rx = DummyReceiver {
+            data: &wire[0..tx_count],
+            rx_count: &mut rx_count,
+        };
+        let mut receiver = Receiver::new(rx);
+        let mut reassembler = Reassembler::<16>::new();
+
+        let _e = receiver
+            .receive_reassembled::<8, 16>(&mut reassembler)
+            .expect_err("Only the final fragment has arrived so far");
+
+        let frame: Frame<16> = receiver
+            .receive_reassembled::<8, 16>(&mut reassembler)
+            .expect("Receive failed!");
+        assert_eq!(0x1337, frame.id());
+        assert_eq!(&[1, 2, 3, 4, 5, 6], frame.bytes());
+    }
 }
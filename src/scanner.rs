@@ -0,0 +1,337 @@
+use crate::{
+    error::Error,
+    frame::{Frame, FrameHeader, END_OF_HEADER, HEADER_MAX_LEN, START_OF_FRAME},
+};
+
+/// Incremental, byte-at-a-time frame parser.
+///
+/// Unlike [`crate::receiver::Receiver`], which needs random access into a
+/// contiguous buffer via [`crate::receiver::ReceiveQueue`], a `FrameScanner`
+/// is fed bytes (or slices of bytes) as they trickle in -- for example one
+/// byte at a time from a UART RX interrupt, or one DMA buffer at a time --
+/// and reports `WouldBlock` until a full frame has been accumulated.
+///
+/// On corruption the scanner automatically resyncs: it drops whatever it
+/// had accumulated so far and starts hunting for the next `START_OF_FRAME`
+/// marker, exactly as [`crate::receiver::Receiver`] does.
+pub struct FrameScanner<const N: usize> {
+    state: State<N>,
+    bytes_skipped: u32,
+}
+
+enum State<const N: usize> {
+    HuntingForStartOfFrame,
+    // The header is variable-length (varint-encoded id and length), so we
+    // accumulate bytes until END_OF_HEADER is seen or HEADER_MAX_LEN bytes
+    // have been read without finding it.
+    ReadingHeader {
+        bytes: [u8; HEADER_MAX_LEN],
+        count: usize,
+    },
+    ReadingBody {
+        header: FrameHeader,
+        data: [u8; N],
+        count: usize,
+    },
+}
+
+impl<const N: usize> Default for FrameScanner<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FrameScanner<N> {
+    pub fn new() -> Self {
+        Self {
+            state: State::HuntingForStartOfFrame,
+            bytes_skipped: 0,
+        }
+    }
+
+    /// Returns total amount of incoming bytes that were discarded.
+    ///
+    /// Mirrors [`crate::receiver::Receiver::bytes_skipped`]: a substantial
+    /// amount of skipped bytes may indicate a bad link quality.
+    pub fn bytes_skipped(&self) -> u32 {
+        self.bytes_skipped
+    }
+
+    fn abandon(&mut self, discarded: usize) {
+        self.bytes_skipped += discarded as u32;
+        self.state = State::HuntingForStartOfFrame;
+    }
+
+    /// Feed a single incoming byte into the scanner.
+    ///
+    /// Returns `WouldBlock` until a full frame has been buffered, `Ok(frame)`
+    /// once the base64 body and CRC validate, or a concrete [`Error`] on
+    /// corruption (after which the scanner has already resynced and is
+    /// ready to hunt for the next frame).
+    pub fn feed(&mut self, byte: u8) -> nb::Result<Frame<N>, Error> {
+        // A START_OF_FRAME byte arriving while we're mid-header/mid-body is
+        // never valid payload for the frame we were accumulating: drop it
+        // and restart header parsing from this byte.
+        if byte == START_OF_FRAME {
+            match &self.state {
+                State::ReadingHeader { count, .. } if *count > 0 => {
+                    let skipped = *count;
+                    self.abandon(skipped);
+                    self.state = State::ReadingHeader {
+                        bytes: Self::header_start(byte),
+                        count: 1,
+                    };
+                    return Err(nb::Error::WouldBlock);
+                }
+                State::ReadingBody { header, count, .. } => {
+                    let skipped = header.header_len() + *count;
+                    self.abandon(skipped);
+                    self.state = State::ReadingHeader {
+                        bytes: Self::header_start(byte),
+                        count: 1,
+                    };
+                    return Err(nb::Error::WouldBlock);
+                }
+                _ => {}
+            }
+        }
+
+        let state = core::mem::replace(&mut self.state, State::HuntingForStartOfFrame);
+
+        match state {
+            State::HuntingForStartOfFrame => {
+                if byte == START_OF_FRAME {
+                    self.state = State::ReadingHeader {
+                        bytes: Self::header_start(byte),
+                        count: 1,
+                    };
+                } else {
+                    self.bytes_skipped += 1;
+                }
+                Err(nb::Error::WouldBlock)
+            }
+            State::ReadingHeader { mut bytes, mut count } => {
+                if count >= HEADER_MAX_LEN {
+                    // No END_OF_HEADER marker within the maximum header size: corrupt.
+                    self.abandon(count);
+                    return Err(nb::Error::Other(Error::InvalidHeader));
+                }
+
+                bytes[count] = byte;
+                count += 1;
+
+                if byte != END_OF_HEADER {
+                    self.state = State::ReadingHeader { bytes, count };
+                    return Err(nb::Error::WouldBlock);
+                }
+
+                match FrameHeader::try_from(&bytes[0..count]) {
+                    Ok(header) => {
+                        // `data` accumulates the wire-encoded (base64 + CRC)
+                        // body, which is `data_len()` bytes -- larger than
+                        // the decoded `payload_len()` -- so that's the bound
+                        // that must fit in the `N`-byte buffer.
+                        if header.data_len() > N {
+                            self.abandon(count);
+                            return Err(nb::Error::Other(Error::TooManyBytes));
+                        }
+                        if header.data_len() == 0 {
+                            self.state = State::HuntingForStartOfFrame;
+                            return match Frame::try_from((header, &[][..])) {
+                                Ok(frame) => Ok(frame),
+                                Err(e) => Err(nb::Error::Other(e)),
+                            };
+                        }
+                        self.state = State::ReadingBody {
+                            header,
+                            data: [0; N],
+                            count: 0,
+                        };
+                        Err(nb::Error::WouldBlock)
+                    }
+                    Err(e) => {
+                        self.abandon(count);
+                        Err(nb::Error::Other(e))
+                    }
+                }
+            }
+            State::ReadingBody { header, mut data, mut count } => {
+                data[count] = byte;
+                count += 1;
+
+                let b64_len = header.data_len();
+                if count < b64_len {
+                    self.state = State::ReadingBody { header, data, count };
+                    return Err(nb::Error::WouldBlock);
+                }
+
+                let frame_len = header.header_len() + count;
+                match Frame::try_from((header, &data[0..b64_len])) {
+                    Ok(frame) => Ok(frame),
+                    Err(e) => {
+                        self.abandon(frame_len);
+                        Err(nb::Error::Other(e))
+                    }
+                }
+            }
+        }
+    }
+
+    fn header_start(first_byte: u8) -> [u8; HEADER_MAX_LEN] {
+        let mut bytes = [0_u8; HEADER_MAX_LEN];
+        bytes[0] = first_byte;
+        bytes
+    }
+
+    /// Feed a slice of incoming bytes into the scanner.
+    ///
+    /// This simply calls [`Self::feed`] for every byte in `bytes`. If a
+    /// frame completes (or a corruption is detected) partway through the
+    /// slice, the remaining bytes are still fed into the scanner so no
+    /// input is lost; the *last* non-`WouldBlock` outcome observed while
+    /// processing the slice is returned.
+    pub fn feed_slice(&mut self, bytes: &[u8]) -> nb::Result<Frame<N>, Error> {
+        let mut result = Err(nb::Error::WouldBlock);
+        for byte in bytes {
+            match self.feed(*byte) {
+                Err(nb::Error::WouldBlock) => {}
+                other => result = other,
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameScanner;
+    use crate::error::Error;
+    use crate::frame::{END_OF_HEADER, START_OF_FRAME};
+    use crate::transmitter::{TransmitQueue, Transmitter};
+
+    // Frame header for id=0x1337, type=Data, offset=0, length=7: START_OF_FRAME,
+    // varint(0x1337) = [0x77, 0x4C, 0x01], 0x20, varint(0) = [0x00], varint(7) = [0x07], END_OF_HEADER
+    fn valid_frame_bytes() -> [u8; 15] {
+        [
+            START_OF_FRAME,
+            0x77,
+            0x4C,
+            0x01,
+            0x20,
+            0x00,
+            0x07,
+            END_OF_HEADER,
+            0x41,
+            0x41,
+            0x45,
+            0x43,
+            0x44,
+            0x6D,
+            0x34,
+        ]
+    }
+
+    #[test]
+    fn feed_byte_at_a_time_works() {
+        let mut scanner = FrameScanner::<128>::new();
+        let bytes = valid_frame_bytes();
+
+        for byte in &bytes[0..bytes.len() - 1] {
+            let err = scanner.feed(*byte).expect_err("Should still need more data");
+            assert_eq!(nb::Error::WouldBlock, err);
+        }
+
+        let frame = scanner.feed(bytes[bytes.len() - 1]).expect("Valid frame");
+        assert_eq!(0x1337, frame.id());
+        assert_eq!(&[0, 1, 2], frame.bytes());
+        assert_eq!(0, scanner.bytes_skipped());
+    }
+
+    #[test]
+    fn feed_slice_works() {
+        let mut scanner = FrameScanner::<128>::new();
+        let frame = scanner
+            .feed_slice(&valid_frame_bytes())
+            .expect("Valid frame");
+        assert_eq!(0x1337, frame.id());
+        assert_eq!(&[0, 1, 2], frame.bytes());
+    }
+
+    #[test]
+    fn garbage_before_frame_is_skipped() {
+        let mut scanner = FrameScanner::<128>::new();
+        let mut bytes = vec![0x00, 0x11, 0x22];
+        bytes.extend_from_slice(&valid_frame_bytes());
+
+        let frame = scanner.feed_slice(&bytes).expect("Valid frame");
+        assert_eq!(0x1337, frame.id());
+        assert_eq!(3, scanner.bytes_skipped());
+    }
+
+    #[test]
+    fn start_of_frame_mid_header_restarts() {
+        let mut scanner = FrameScanner::<128>::new();
+        let mut bytes = vec![START_OF_FRAME, 0xAB];
+        bytes.extend_from_slice(&valid_frame_bytes());
+
+        let frame = scanner.feed_slice(&bytes).expect("Valid frame");
+        assert_eq!(0x1337, frame.id());
+    }
+
+    #[test]
+    fn invalid_crc_resyncs_and_reports_error() {
+        let mut scanner = FrameScanner::<128>::new();
+        let mut bytes = valid_frame_bytes();
+        bytes[13] = 0x42; // corrupt CRC byte
+
+        let err = scanner.feed_slice(&bytes).expect_err("CRC should mismatch");
+        assert_eq!(nb::Error::Other(Error::InvalidCRC), err);
+
+        // Scanner should have resynced and be ready for the next frame
+        let frame = scanner
+            .feed_slice(&valid_frame_bytes())
+            .expect("Valid frame");
+        assert_eq!(0x1337, frame.id());
+    }
+
+    struct VecTransmitter<'a> {
+        data: &'a mut [u8],
+        tx_count: &'a mut usize,
+    }
+    impl TransmitQueue for VecTransmitter<'_> {
+        fn space_available(&self) -> usize {
+            self.data.len() - *self.tx_count
+        }
+
+        fn write(&mut self, byte: u8) -> Result<(), u8> {
+            self.data[*self.tx_count] = byte;
+            *self.tx_count += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn body_exceeding_buffer_is_rejected_not_panicked() {
+        // A 100-byte payload base64+CRC-encodes to well over 100 bytes on
+        // the wire, so a `FrameScanner::<100>`'s body buffer -- sized to the
+        // *decoded* capacity `N` -- can't hold it even though the decoded
+        // payload itself would fit exactly.
+        let mut wire = [0_u8; 256];
+        let mut tx_count: usize = 0;
+        let tx = VecTransmitter {
+            data: &mut wire,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+        let payload = [0x42_u8; 100];
+        transmitter
+            .transmit(0x1337, &payload)
+            .expect("Transmit failed!");
+
+        let mut scanner = FrameScanner::<100>::new();
+        let err = scanner
+            .feed_slice(&wire[0..tx_count])
+            .expect_err("Oversized body should be rejected, not panic");
+        assert_eq!(nb::Error::Other(Error::TooManyBytes), err);
+    }
+}
@@ -1,8 +1,18 @@
-use crate::{error::Error, frame};
+use crate::{
+    error::Error,
+    flow_control::{SenderFlowControl, DEFAULT_WINDOW},
+    frame,
+};
 use crc::{Crc, CRC_16_USB};
 
+// Process data in blocks so we can handle arbitrary input data length
+// NB: BLOCK_SIZE must be a multiple of 3 (3 bytes encode into exactly 4 output characters)
+const BLOCK_SIZE: usize = 30;
+
 pub struct Transmitter<TX> {
     tx: TX,
+    state: TransmitState,
+    flow_control: SenderFlowControl,
 }
 
 pub trait TransmitQueue {
@@ -10,83 +20,301 @@ pub trait TransmitQueue {
     fn write(&mut self, byte: u8) -> Result<(), u8>;
 }
 
+/// Tracks progress of a `transmit()` call that could not be finished in one
+/// go because `space_available()` ran out partway through the frame.
+///
+/// A later call to `transmit()` with the same `packet_id` and `data` resumes
+/// exactly where the previous call stopped: the header/body byte offset, the
+/// base64 block index, and the pre-computed CRC bytes are all preserved so
+/// nothing is recomputed over data that has already been sent.
+enum TransmitState {
+    Idle,
+    Sending {
+        packet_id: u32,
+        frame_type: frame::FrameType,
+        compressed: bool,
+        offset: u32,
+        final_fragment: bool,
+        data_len: usize,
+        checksum: [u8; 2],
+        header: [u8; frame::HEADER_MAX_LEN],
+        header_len: usize,
+        header_sent: usize,
+        block_index: usize,
+        pending: [u8; BLOCK_SIZE * 2],
+        pending_len: usize,
+        pending_sent: usize,
+    },
+}
+
 impl<TX> Transmitter<TX>
 where
     TX: TransmitQueue,
 {
+    /// Create a `Transmitter` assuming [`DEFAULT_WINDOW`] bytes of
+    /// flow-control credit until a `WINDOW_UPDATE` says otherwise. Use
+    /// [`Self::with_window`] if the peer is known to advertise a different
+    /// amount by default.
     pub fn new(tx: TX) -> Self {
-        Self { tx }
+        Self::with_window(tx, DEFAULT_WINDOW)
     }
 
-    fn write(&mut self, byte: u8) -> nb::Result<(), crate::error::Error> {
-        match self.tx.write(byte) {
-            Ok(_) => Ok(()),
-            Err(_) => return Err(nb::Error::Other(Error::QueueOverflow)),
+    /// Like [`Self::new`], but assumes `initial_max_data` bytes of
+    /// flow-control credit instead of [`DEFAULT_WINDOW`] -- see
+    /// [`crate::flow_control`].
+    pub fn with_window(tx: TX, initial_max_data: u64) -> Self {
+        Self {
+            tx,
+            state: TransmitState::Idle,
+            flow_control: SenderFlowControl::new(initial_max_data),
         }
     }
 
+    /// Apply a `WINDOW_UPDATE` credit update received from the peer -- see
+    /// [`crate::flow_control`] and [`crate::receiver::Receiver::poll_window_update`].
+    pub fn apply_window_update(&mut self, max_data: u32) {
+        self.flow_control.on_window_update(max_data);
+    }
+
     pub fn transmit_frame<const N: usize>(
         &mut self,
         frame: &frame::Frame<N>,
     ) -> nb::Result<(), crate::error::Error> {
-        self.transmit(frame.id(), frame.bytes())
+        self.transmit_typed(frame.id(), frame.frame_type(), false, frame.bytes())
     }
 
-    pub fn transmit(&mut self, packet_id: u16, data: &[u8]) -> nb::Result<(), crate::error::Error> {
-        let header = match frame::FrameHeader::new(packet_id, data.len()) {
-            Ok(frame) => frame,
-            Err(e) => return Err(nb::Error::Other(e)),
-        };
+    /// Write as much of a `Data` frame as currently fits in the `TransmitQueue`.
+    ///
+    /// Returns `WouldBlock` if `space_available()` runs out before the whole
+    /// frame has been written; internal progress is preserved, so calling
+    /// `transmit()` again with the *same* `packet_id` and `data` continues
+    /// from exactly where this call left off rather than starting over.
+    pub fn transmit(&mut self, packet_id: u32, data: &[u8]) -> nb::Result<(), crate::error::Error> {
+        self.transmit_typed(packet_id, frame::FrameType::Data, false, data)
+    }
+
+    /// Like [`Self::transmit`], but first tries to shrink `data` with
+    /// [`crate::compress`] using `scratch` as scratch space. Falls back to
+    /// sending `data` uncompressed when compression doesn't help (or
+    /// `scratch` is too small to hold the result).
+    ///
+    /// As with `transmit()`, a call that returns `WouldBlock` must be retried
+    /// with the exact same `packet_id`, `data` and `scratch` to resume.
+    #[cfg(feature = "compression")]
+    pub fn transmit_compressed(
+        &mut self,
+        packet_id: u32,
+        data: &[u8],
+        scratch: &mut [u8],
+    ) -> nb::Result<(), crate::error::Error> {
+        match crate::compress::compress(data, scratch) {
+            Some(compressed) => {
+                self.transmit_typed(packet_id, frame::FrameType::Data, true, compressed)
+            }
+            None => self.transmit_typed(packet_id, frame::FrameType::Data, false, data),
+        }
+    }
+
+    /// Send one fragment of a logical message that spans multiple frames --
+    /// see [`crate::reassembly`]. `offset` is this fragment's byte offset
+    /// within the reassembled message, and `final_fragment` marks the
+    /// fragment that completes it.
+    ///
+    /// As with `transmit()`, a call that returns `WouldBlock` must be
+    /// retried with the exact same arguments to resume.
+    pub fn transmit_fragment(
+        &mut self,
+        packet_id: u32,
+        offset: u32,
+        final_fragment: bool,
+        data: &[u8],
+    ) -> nb::Result<(), crate::error::Error> {
+        self.transmit_typed_fragment(
+            packet_id,
+            frame::FrameType::Data,
+            false,
+            offset,
+            final_fragment,
+            data,
+        )
+    }
+
+    fn transmit_typed(
+        &mut self,
+        packet_id: u32,
+        frame_type: frame::FrameType,
+        compressed: bool,
+        data: &[u8],
+    ) -> nb::Result<(), crate::error::Error> {
+        self.transmit_typed_fragment(packet_id, frame_type, compressed, 0, true, data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn transmit_typed_fragment(
+        &mut self,
+        packet_id: u32,
+        frame_type: frame::FrameType,
+        compressed: bool,
+        offset: u32,
+        final_fragment: bool,
+        data: &[u8],
+    ) -> nb::Result<(), crate::error::Error> {
+        let resume = matches!(&self.state,
+            TransmitState::Sending { packet_id: id, frame_type: ty, compressed: c, offset: o, final_fragment: f, data_len, .. }
+                if *id == packet_id && *ty == frame_type && *c == compressed
+                    && *o == offset && *f == final_fragment && *data_len == data.len());
+
+        if !resume {
+            // Only `Data` payloads are subject to flow control -- control
+            // frames (including the `WindowUpdate` that raises this very
+            // limit) must always be able to get through.
+            if frame_type == frame::FrameType::Data
+                && data.len() as u64 > self.flow_control.available()
+            {
+                return Err(nb::Error::WouldBlock);
+            }
 
-        if self.tx.space_available() < header.total_packet_len() {
-            return Err(nb::Error::WouldBlock);
+            let header = match frame::FrameHeader::new_fragment(
+                packet_id,
+                frame_type,
+                compressed,
+                offset,
+                final_fragment,
+                data.len(),
+            ) {
+                Ok(header) => header,
+                Err(e) => return Err(nb::Error::Other(e)),
+            };
+
+            // CRC16 checksum is calculated over all input data (before base64 encoding).
+            // Computed once up front so a resumed call never has to re-walk
+            // data that was already sent.
+            let crc = Crc::<u16>::new(&CRC_16_USB);
+            let mut checksum = crc.digest();
+            checksum.update(data);
+            let checksum = checksum.finalize().to_le_bytes();
+
+            let (header, header_len) = header.as_bytes();
+
+            self.state = TransmitState::Sending {
+                packet_id,
+                frame_type,
+                compressed,
+                offset,
+                final_fragment,
+                data_len: data.len(),
+                checksum,
+                header,
+                header_len,
+                header_sent: 0,
+                block_index: 0,
+                pending: [0; BLOCK_SIZE * 2],
+                pending_len: 0,
+                pending_sent: 0,
+            };
+
+            if frame_type == frame::FrameType::Data {
+                self.flow_control.reserve(data.len());
+            }
         }
 
-        // Write header
-        let header = header.as_bytes();
-        for byte in header {
-            self.write(byte)?;
+        let (checksum, header, header_len, header_sent, block_index, pending, pending_len, pending_sent) =
+            match &mut self.state {
+                TransmitState::Sending {
+                    checksum,
+                    header,
+                    header_len,
+                    header_sent,
+                    block_index,
+                    pending,
+                    pending_len,
+                    pending_sent,
+                    ..
+                } => (
+                    checksum,
+                    header,
+                    header_len,
+                    header_sent,
+                    block_index,
+                    pending,
+                    pending_len,
+                    pending_sent,
+                ),
+                TransmitState::Idle => unreachable!("state was just set to Sending above"),
+            };
+
+        // `space_available()` reflects the queue's free space *right now*; it
+        // is sampled once per call and spent down locally as bytes are
+        // written, rather than re-polled before every byte, so a queue that
+        // always reports the same small constant (e.g. a little hardware
+        // FIFO) still bounds a single call to that many bytes.
+        let mut budget = self.tx.space_available();
+
+        // Write as many header bytes as currently fit
+        while *header_sent < *header_len {
+            if budget == 0 {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.tx
+                .write(header[*header_sent])
+                .map_err(|_| nb::Error::Other(Error::QueueOverflow))?;
+            *header_sent += 1;
+            budget -= 1;
         }
 
-        // CRC16 checksum is calculated over all input data (before base64 encoding)
-        let crc = Crc::<u16>::new(&CRC_16_USB);
-        let mut checksum = crc.digest();
-        checksum.update(data);
-        let checksum = checksum.finalize().to_le_bytes();
+        if data.is_empty() {
+            self.state = TransmitState::Idle;
+            return Ok(());
+        }
 
-        // Process data in blocks so we can handle arbitrary input data length
-        // NB: BLOCK_SIZE must be a multiple of 3 (3 bytes encode into exactly 4 output characters)
-        const BLOCK_SIZE: usize = 30;
+        let total_blocks = (data.len() - 1) / BLOCK_SIZE + 1;
         let base64_cfg = base64::Config::new(base64::CharacterSet::Standard, false);
-        for offset in (0..data.len()).step_by(BLOCK_SIZE) {
-            let end_index = offset + BLOCK_SIZE;
-
-            let mut encoded: [u8; BLOCK_SIZE * 2] = [0; BLOCK_SIZE * 2];
-
-            let encoded_size = if (end_index + 1) < data.len() {
-                let input = &data[offset..end_index];
-                base64::encode_config_slice(input, base64_cfg, &mut encoded)
-
-            // Last block: combine data + CRC before base64 encoding
-            } else {
-                let input = &data[offset..];
-                let in_len = input.len();
-                let mut tmp: [u8; BLOCK_SIZE + 2] = [0; BLOCK_SIZE + 2];
-                for (i, byte) in input.iter().enumerate() {
-                    tmp[i] = *byte;
+
+        loop {
+            // Encode the next block once the previous one has been fully flushed
+            if *pending_sent >= *pending_len {
+                if *block_index >= total_blocks {
+                    break;
                 }
-                tmp[in_len] = checksum[0];
-                tmp[in_len + 1] = checksum[1];
 
-                base64::encode_config_slice(&tmp[0..in_len + 2], base64_cfg, &mut encoded)
-            };
+                let offset = *block_index * BLOCK_SIZE;
+                let end_index = offset + BLOCK_SIZE;
+
+                let encoded_size = if end_index < data.len() {
+                    let input = &data[offset..end_index];
+                    base64::encode_config_slice(input, base64_cfg, pending)
+
+                // Last block: combine data + CRC before base64 encoding
+                } else {
+                    let input = &data[offset..];
+                    let in_len = input.len();
+                    let mut tmp: [u8; BLOCK_SIZE + 2] = [0; BLOCK_SIZE + 2];
+                    tmp[0..in_len].copy_from_slice(input);
+                    tmp[in_len] = checksum[0];
+                    tmp[in_len + 1] = checksum[1];
 
-            // Write base64-encoded data
-            for byte in &encoded[0..encoded_size] {
-                self.write(*byte)?;
+                    base64::encode_config_slice(&tmp[0..in_len + 2], base64_cfg, pending)
+                };
+
+                *pending_len = encoded_size;
+                *pending_sent = 0;
+                *block_index += 1;
+            }
+
+            // Write as many of the pending encoded bytes as currently fit
+            while *pending_sent < *pending_len {
+                if budget == 0 {
+                    return Err(nb::Error::WouldBlock);
+                }
+                self.tx
+                    .write(pending[*pending_sent])
+                    .map_err(|_| nb::Error::Other(Error::QueueOverflow))?;
+                *pending_sent += 1;
+                budget -= 1;
             }
         }
 
+        self.state = TransmitState::Idle;
         Ok(())
     }
 }
@@ -117,6 +345,30 @@ mod tests {
         }
     }
 
+    /// Like `DummyTransmitter`, but only ever reports a small, fixed amount
+    /// of free space -- modeling a small hardware FIFO that must be drained
+    /// (here: instantly, for test purposes) between `transmit()` calls.
+    struct SmallFifoTransmitter<'a> {
+        data: &'a mut [u8; 0xFFFF],
+        tx_count: &'a mut usize,
+        fifo_size: usize,
+    }
+    impl TransmitQueue for SmallFifoTransmitter<'_> {
+        fn space_available(&self) -> usize {
+            self.fifo_size.min(0xFFFF_usize - *self.tx_count)
+        }
+
+        fn write(&mut self, byte: u8) -> Result<(), u8> {
+            if *self.tx_count >= 0xFFFF_usize {
+                return Err(byte);
+            }
+
+            self.data[*self.tx_count] = byte;
+            *self.tx_count += 1;
+            Ok(())
+        }
+    }
+
     #[test]
     fn transmit_works() {
         let mut data = [0; 0xFFFF];
@@ -129,26 +381,28 @@ mod tests {
         transmitter
             .transmit(0x1337, &[0x0, 0x1, 0x2])
             .expect("Transmit failed!");
-        assert_eq!(6 + 7, tx_count, "Expect 13-byte message"); // 6-byte header + 8/6 * (3-byte data + 2-byte CRC)
+        assert_eq!(8 + 7, tx_count, "Expect 15-byte message"); // 8-byte header + 8/6 * (3-byte data + 2-byte CRC)
 
-        // Frame header
+        // Frame header: START_OF_FRAME, varint(id=0x1337), frame type, varint(offset=0), varint(length=7), END_OF_HEADER
         assert_eq!(data[0], START_OF_FRAME); // Start-of-frame marker
-        assert_eq!(data[1], 0x37); // packet ID 0x1337 as little-endian (low byte)
-        assert_eq!(data[2], 0x13); // packet ID 0x1337 as little-endian (high byte)
-        assert_eq!(data[3], 0x07); // Packet length 7 (4-byte data + 3-byte CRC) (low byte)
-        assert_eq!(data[4], 0x00); // Packet length 7 (4-byte data + 3-byte CRC) (high byte)
-        assert_eq!(data[5], END_OF_HEADER); // End-of-header marker
+        assert_eq!(data[1], 0x77); // varint(0x1337) byte 0
+        assert_eq!(data[2], 0x4C); // varint(0x1337) byte 1
+        assert_eq!(data[3], 0x01); // varint(0x1337) byte 2 (terminator)
+        assert_eq!(data[4], 0x20); // frame type: Data, FINAL_FRAGMENT_FLAG set
+        assert_eq!(data[5], 0x00); // varint(offset = 0) (terminator)
+        assert_eq!(data[6], 0x07); // varint(length = 7) (terminator)
+        assert_eq!(data[7], END_OF_HEADER); // End-of-header marker
 
         // base64-encoded [00, 01, 02] should be "AAEC" = [0x41, 0x41, 0x45, 0x43]
-        assert_eq!(data[6], 0x41);
-        assert_eq!(data[7], 0x41);
-        assert_eq!(data[8], 0x45);
-        assert_eq!(data[9], 0x43);
+        assert_eq!(data[8], 0x41);
+        assert_eq!(data[9], 0x41);
+        assert_eq!(data[10], 0x45);
+        assert_eq!(data[11], 0x43);
 
         // CRC16-USB over [00, 01, 02] should be 0x6E0E = [0x0E, 0x6E] (little-endian) = "Dm4"
-        assert_eq!(data[10], 0x44);
-        assert_eq!(data[11], 0x6D);
-        assert_eq!(data[12], 0x34);
+        assert_eq!(data[12], 0x44);
+        assert_eq!(data[13], 0x6D);
+        assert_eq!(data[14], 0x34);
     }
 
     #[test]
@@ -166,26 +420,28 @@ mod tests {
         transmitter
             .transmit_frame(&frame)
             .expect("Transmit failed!");
-        assert_eq!(6 + 7, tx_count, "Expect 13-byte message"); // 6-byte header + 8/6 * (3-byte data + 2-byte CRC)
+        assert_eq!(8 + 7, tx_count, "Expect 15-byte message"); // 8-byte header + 8/6 * (3-byte data + 2-byte CRC)
 
-        // Frame header
+        // Frame header: START_OF_FRAME, varint(id=0x1337), frame type, varint(offset=0), varint(length=7), END_OF_HEADER
         assert_eq!(data[0], START_OF_FRAME); // Start-of-frame marker
-        assert_eq!(data[1], 0x37); // packet ID 0x1337 as little-endian (low byte)
-        assert_eq!(data[2], 0x13); // packet ID 0x1337 as little-endian (high byte)
-        assert_eq!(data[3], 0x07); // Packet length 7 (4-byte data + 3-byte CRC) (low byte)
-        assert_eq!(data[4], 0x00); // Packet length 7 (4-byte data + 3-byte CRC) (high byte)
-        assert_eq!(data[5], END_OF_HEADER); // End-of-header marker
+        assert_eq!(data[1], 0x77); // varint(0x1337) byte 0
+        assert_eq!(data[2], 0x4C); // varint(0x1337) byte 1
+        assert_eq!(data[3], 0x01); // varint(0x1337) byte 2 (terminator)
+        assert_eq!(data[4], 0x20); // frame type: Data, FINAL_FRAGMENT_FLAG set
+        assert_eq!(data[5], 0x00); // varint(offset = 0) (terminator)
+        assert_eq!(data[6], 0x07); // varint(length = 7) (terminator)
+        assert_eq!(data[7], END_OF_HEADER); // End-of-header marker
 
         // base64-encoded [00, 01, 02] should be "AAEC" = [0x41, 0x41, 0x45, 0x43]
-        assert_eq!(data[6], 0x41);
-        assert_eq!(data[7], 0x41);
-        assert_eq!(data[8], 0x45);
-        assert_eq!(data[9], 0x43);
+        assert_eq!(data[8], 0x41);
+        assert_eq!(data[9], 0x41);
+        assert_eq!(data[10], 0x45);
+        assert_eq!(data[11], 0x43);
 
         // CRC16-USB over [00, 01, 02] should be 0x6E0E = [0x0E, 0x6E] (little-endian) = "Dm4"
-        assert_eq!(data[10], 0x44);
-        assert_eq!(data[11], 0x6D);
-        assert_eq!(data[12], 0x34);
+        assert_eq!(data[12], 0x44);
+        assert_eq!(data[13], 0x6D);
+        assert_eq!(data[14], 0x34);
     }
 
     #[test]
@@ -208,20 +464,23 @@ mod tests {
             )
             .expect("Transmit failed!");
         // 58 bytes = 78+2 bytes of base64
-        assert_eq!(6 + 78 + 2, tx_count, "Expect 80-byte message");
-        assert_eq!(0, data[6 + 78 + 2]);
-        // Frame header
+        assert_eq!(9 + 78 + 2, tx_count, "Expect 89-byte message");
+        assert_eq!(0, data[9 + 78 + 2]);
+        // Frame header: START_OF_FRAME, varint(id=0x1337), frame type, varint(offset=0), varint(length=80), END_OF_HEADER
         assert_eq!(data[0], START_OF_FRAME); // Start-of-frame marker
-        assert_eq!(data[1], 0x37); // packet ID 0x1337 as little-endian (low byte)
-        assert_eq!(data[2], 0x13); // packet ID 0x1337 as little-endian (high byte)
-        assert_eq!(data[3], 78 + 2); // Length of encoded data (low byte)
-        assert_eq!(data[4], 0x00); // Length of encoded data (high byte)
-        assert_eq!(data[5], END_OF_HEADER); // End-of-header marker
+        assert_eq!(data[1], 0x77); // varint(0x1337) byte 0
+        assert_eq!(data[2], 0x4C); // varint(0x1337) byte 1
+        assert_eq!(data[3], 0x01); // varint(0x1337) byte 2 (terminator)
+        assert_eq!(data[4], 0x20); // frame type: Data, FINAL_FRAGMENT_FLAG set
+        assert_eq!(data[5], 0x00); // varint(offset = 0) (terminator)
+        assert_eq!(data[6], 0x50); // varint(length = 80) byte 0
+        assert_eq!(data[7], 0x01); // varint(length = 80) byte 1 (terminator)
+        assert_eq!(data[8], END_OF_HEADER); // End-of-header marker
 
         // (expect CRC = 0x8F53 == 36691)
 
         // Should be possible to create a valid frame from these bytes
-        let _frame: Frame<128> = Frame::try_from(&data[0..6 + 78 + 2]).expect("Invalid packet");
+        let _frame: Frame<128> = Frame::try_from(&data[0..9 + 78 + 2]).expect("Invalid packet");
     }
 
     #[test]
@@ -234,19 +493,188 @@ mod tests {
         };
         let mut transmitter = Transmitter::new(tx);
         transmitter.transmit(0x1337, &[]).expect("Transmit failed!");
-        assert_eq!(6, tx_count, "Expect 6-byte message");
+        assert_eq!(8, tx_count, "Expect 8-byte message");
 
-        // Frame header
+        // Frame header: START_OF_FRAME, varint(id=0x1337), frame type, varint(offset=0), varint(length=0), END_OF_HEADER
         assert_eq!(data[0], START_OF_FRAME); // Start-of-frame marker
-        assert_eq!(data[1], 0x37); // packet ID 0x1337 as little-endian (low byte)
-        assert_eq!(data[2], 0x13); // packet ID 0x1337 as little-endian (high byte)
-        assert_eq!(data[3], 0x00); // Length of encoded data (low byte)
-        assert_eq!(data[4], 0x00); // Length of encoded data (high byte)
-        assert_eq!(data[5], END_OF_HEADER); // End-of-header marker
+        assert_eq!(data[1], 0x77); // varint(0x1337) byte 0
+        assert_eq!(data[2], 0x4C); // varint(0x1337) byte 1
+        assert_eq!(data[3], 0x01); // varint(0x1337) byte 2 (terminator)
+        assert_eq!(data[4], 0x20); // frame type: Data, FINAL_FRAGMENT_FLAG set
+        assert_eq!(data[5], 0x00); // varint(offset = 0) (terminator)
+        assert_eq!(data[6], 0x00); // varint(length = 0) (terminator)
+        assert_eq!(data[7], END_OF_HEADER); // End-of-header marker
 
-        assert_eq!(data[6], 0xBE); // Should not be written to
+        assert_eq!(data[8], 0xBE); // Should not be written to
 
         // Should be possible to create a valid frame from these bytes
-        let _frame: Frame<128> = Frame::try_from(&data[0..6]).expect("Invalid packet");
+        let _frame: Frame<128> = Frame::try_from(&data[0..8]).expect("Invalid packet");
+    }
+
+    #[test]
+    fn transmit_resumes_across_multiple_calls_on_small_fifo() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = SmallFifoTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+            fifo_size: 4,
+        };
+        let mut transmitter = Transmitter::new(tx);
+
+        let payload = [0x0_u8, 0x1, 0x2];
+        let mut calls = 0;
+        loop {
+            calls += 1;
+            match transmitter.transmit(0x1337, &payload) {
+                Ok(()) => break,
+                Err(nb::Error::WouldBlock) => continue,
+                Err(nb::Error::Other(e)) => panic!("Unexpected error: {:?}", e),
+            }
+        }
+
+        // 15-byte frame split across a 4-byte-at-a-time FIFO takes several calls
+        assert!(calls > 1, "Expected transmit to require multiple calls");
+        assert_eq!(8 + 7, tx_count, "Expect 15-byte message");
+
+        let _frame: Frame<128> = Frame::try_from(&data[0..8 + 7]).expect("Invalid packet");
+    }
+
+    #[test]
+    fn transmit_fragment_works() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+        transmitter
+            .transmit_fragment(0x1337, 3, false, &[0x3, 0x4, 0x5])
+            .expect("Transmit failed!");
+        assert_eq!(8 + 7, tx_count, "Expect 15-byte message");
+
+        // Frame header: START_OF_FRAME, varint(id=0x1337), frame type, varint(offset=3), varint(length=7), END_OF_HEADER
+        assert_eq!(data[0], START_OF_FRAME); // Start-of-frame marker
+        assert_eq!(data[1], 0x77); // varint(0x1337) byte 0
+        assert_eq!(data[2], 0x4C); // varint(0x1337) byte 1
+        assert_eq!(data[3], 0x01); // varint(0x1337) byte 2 (terminator)
+        assert_eq!(data[4], 0x00); // frame type: Data, FINAL_FRAGMENT_FLAG clear (not the final fragment)
+        assert_eq!(data[5], 0x03); // varint(offset = 3) (terminator)
+        assert_eq!(data[6], 0x07); // varint(length = 7) (terminator)
+        assert_eq!(data[7], END_OF_HEADER); // End-of-header marker
+    }
+
+    #[test]
+    fn transmit_blocks_once_flow_control_credit_exhausted() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::with_window(tx, 3);
+
+        transmitter
+            .transmit(0x1337, &[0x0, 0x1, 0x2])
+            .expect("Should fit exactly in the initial window");
+
+        let err = transmitter
+            .transmit(0x1338, &[0x3])
+            .expect_err("Credit is exhausted");
+        assert_eq!(nb::Error::WouldBlock, err);
+    }
+
+    #[test]
+    fn transmit_resumes_after_window_update() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::with_window(tx, 3);
+
+        transmitter
+            .transmit(0x1337, &[0x0, 0x1, 0x2])
+            .expect("Should fit exactly in the initial window");
+        transmitter
+            .transmit(0x1338, &[0x3])
+            .expect_err("Credit is exhausted");
+
+        transmitter.apply_window_update(4);
+        transmitter
+            .transmit(0x1338, &[0x3])
+            .expect("Should fit after the window update");
+    }
+
+    #[test]
+    fn transmit_control_frames_bypass_flow_control() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::with_window(tx, 0);
+
+        let frame = Frame::<4>::ping(0x1337, &[1, 2]).expect("Valid frame");
+        transmitter
+            .transmit_frame(&frame)
+            .expect("Control frames aren't subject to flow control");
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn transmit_compressed_shrinks_compressible_payload() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+
+        // Highly repetitive, so it's guaranteed to actually shrink.
+        let payload = [0x42_u8; 256];
+        let mut scratch = [0_u8; 256];
+        transmitter
+            .transmit_compressed(0x1337, &payload, &mut scratch)
+            .expect("Transmit failed!");
+
+        // The wire representation must be smaller than sending `payload` uncompressed would be.
+        assert!(tx_count < 7 + payload.len());
+
+        // `N` exactly matches the payload size: this is the case that used
+        // to panic/fail decompression, since `heatshrink` needs a byte of
+        // headroom above the decompressed length that `Frame<N>`'s own
+        // `data` can't provide -- see the note on `inflate` in `frame.rs`.
+        let frame: Frame<256> = Frame::try_from(&data[0..tx_count]).expect("Invalid packet");
+        assert_eq!(&payload[..], frame.bytes());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn transmit_compressed_falls_back_for_incompressible_payload() {
+        let mut data = [0; 0xFFFF];
+        let mut tx_count: usize = 0;
+        let tx = DummyTransmitter {
+            data: &mut data,
+            tx_count: &mut tx_count,
+        };
+        let mut transmitter = Transmitter::new(tx);
+
+        // Too short to compress into anything smaller than itself.
+        let payload = [0x0_u8, 0x1, 0x2];
+        let mut scratch = [0_u8; 3];
+        transmitter
+            .transmit_compressed(0x1337, &payload, &mut scratch)
+            .expect("Transmit failed!");
+
+        // Falls back to the same uncompressed wire format as transmit_works().
+        assert_eq!(data[4], 0x20); // frame type: Data, COMPRESSED_FLAG clear, FINAL_FRAGMENT_FLAG set
+
+        let frame: Frame<128> = Frame::try_from(&data[0..tx_count]).expect("Invalid packet");
+        assert_eq!(&payload[..], frame.bytes());
     }
 }